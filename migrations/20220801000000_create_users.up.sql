@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS users (
+    id VARCHAR PRIMARY KEY,
+    first_name VARCHAR NOT NULL,
+    last_name VARCHAR NOT NULL,
+    birthday_date DATE NOT NULL,
+    city VARCHAR NOT NULL
+);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS users_birthday_date_idx;
+DROP INDEX IF EXISTS users_last_name_idx;
+DROP INDEX IF EXISTS users_city_idx;
@@ -0,0 +1,3 @@
+CREATE INDEX IF NOT EXISTS users_city_idx ON users (city);
+CREATE INDEX IF NOT EXISTS users_last_name_idx ON users (last_name);
+CREATE INDEX IF NOT EXISTS users_birthday_date_idx ON users (birthday_date);
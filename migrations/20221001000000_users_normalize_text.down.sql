@@ -0,0 +1,2 @@
+-- The original spelling is not kept, normalised values stay as they are.
+SELECT 1;
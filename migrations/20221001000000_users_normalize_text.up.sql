@@ -0,0 +1,10 @@
+-- Align rows written before names and cities were normalised by the domain
+-- (NFC, trimmed, single inner spaces, cities in initcap).
+UPDATE users
+SET first_name = regexp_replace(btrim(normalize(first_name, NFC)), '\s+', ' ', 'g'),
+    last_name = regexp_replace(btrim(normalize(last_name, NFC)), '\s+', ' ', 'g'),
+    city = initcap(regexp_replace(btrim(normalize(city, NFC)), '\s+', ' ', 'g')),
+    version = version + 1
+WHERE first_name IS DISTINCT FROM regexp_replace(btrim(normalize(first_name, NFC)), '\s+', ' ', 'g')
+   OR last_name IS DISTINCT FROM regexp_replace(btrim(normalize(last_name, NFC)), '\s+', ' ', 'g')
+   OR city IS DISTINCT FROM initcap(regexp_replace(btrim(normalize(city, NFC)), '\s+', ' ', 'g'));
@@ -0,0 +1 @@
+ALTER TABLE users ALTER COLUMN id TYPE VARCHAR USING id::text;
@@ -0,0 +1,3 @@
+-- Ids have always been generated as uuids, any other value makes the cast fail
+-- and the migration is rolled back untouched.
+ALTER TABLE users ALTER COLUMN id TYPE UUID USING id::uuid;
@@ -0,0 +1,4 @@
+-- Existing users get the migration time, their real creation date is unknown.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE users ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+CREATE INDEX IF NOT EXISTS users_updated_at_idx ON users (updated_at, id);
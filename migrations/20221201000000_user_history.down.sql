@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS user_history;
+DROP FUNCTION IF EXISTS user_history_append_only();
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS user_history (
+    seq BIGSERIAL PRIMARY KEY,
+    user_id UUID NOT NULL,
+    operation VARCHAR NOT NULL,
+    old_values JSONB NULL,
+    new_values JSONB NULL,
+    actor VARCHAR NOT NULL,
+    request_id VARCHAR NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS user_history_user_idx ON user_history (user_id, changed_at, seq);
+
+CREATE OR REPLACE FUNCTION user_history_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'user_history is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER user_history_append_only BEFORE UPDATE OR DELETE ON user_history
+    FOR EACH ROW EXECUTE FUNCTION user_history_append_only();
+
+-- Users created before the history existed start from their current state.
+INSERT INTO user_history (user_id, operation, new_values, actor, request_id, changed_at)
+SELECT id, 'insert', to_jsonb(users), 'migration', 'migration', updated_at FROM users;
@@ -0,0 +1,5 @@
+CREATE OR REPLACE FUNCTION user_history_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'user_history is append-only';
+END;
+$$ LANGUAGE plpgsql;
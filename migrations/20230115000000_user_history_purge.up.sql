@@ -0,0 +1,14 @@
+-- A purge blanks the snapshots of the users it removes, in its own transaction
+-- after `SET LOCAL app.purging = 'on'`. Entries are never deleted and nothing else changes.
+CREATE OR REPLACE FUNCTION user_history_append_only() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE'
+        AND current_setting('app.purging', true) = 'on'
+        AND NEW.old_values IS NULL AND NEW.new_values IS NULL
+        AND (NEW.seq, NEW.user_id, NEW.operation, NEW.actor, NEW.request_id, NEW.changed_at)
+            = (OLD.seq, OLD.user_id, OLD.operation, OLD.actor, OLD.request_id, OLD.changed_at) THEN
+        RETURN NEW;
+    END IF;
+    RAISE EXCEPTION 'user_history is append-only';
+END;
+$$ LANGUAGE plpgsql;
@@ -1,4 +1,8 @@
-use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use actix_web::{HttpResponse, Responder};
+use actix_web::http::StatusCode;
+use actix_web::web::Data;
+use crate::api::problem::Problem;
+use crate::repository::cache::CachedRepository;
 use serde::Serialize;
 use serde::Deserialize;
 
@@ -18,22 +22,51 @@ pub async fn health() -> impl Responder {
     }).unwrap())
 }
 
+/// Hit and miss counts of the repository cache since the start.
+pub async fn cache(cache: Option<Data<CachedRepository>>) -> impl Responder {
+    match cache {
+        Some(cache) => HttpResponse::Ok()
+            .content_type("application/json")
+            .body(serde_json::to_string(&cache.stats()).unwrap()),
+        None => Problem::new(StatusCode::NOT_FOUND, "cache_disabled").detail("CACHE_ENABLED is not set").response(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use actix_web::{App, test, web, http::header::ContentType};
-    use actix_web::body::to_bytes;
-    use actix_web::http::{header, StatusCode};
-    use crate::api::health::{health, Response};
+    use actix_web::{App, test, web};
+    use actix_web::http::StatusCode;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use actix_web::web::Data;
+    use crate::api::health::{cache, health, Response};
+    use crate::repository::cache::{CacheStats, CachedRepository};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::Repository;
 
     #[actix_web::test]
     async fn test_health_route_ok() {
-        let mut app  =
+        let app  =
             test::init_service(App::new().route("/", web::get().to(health))).await;
         let res = test::TestRequest::get()
             .uri("/")
-            .send_request(&mut app).await;
+            .send_request(&app).await;
         assert!(res.status().is_success());
         let result:Response = test::read_body_json(res).await;
         assert_eq!(result,(Response{ status: "OK".parse().unwrap()}));
     }
-}
\ No newline at end of file
+
+    #[actix_web::test]
+    async fn test_cache_route() {
+        let app = test::init_service(App::new().route("/", web::get().to(cache))).await;
+        let res = test::TestRequest::get().uri("/").send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let repo = CachedRepository::new(Arc::new(InMemoryRepository::new()) as Arc<dyn Repository>, Duration::from_secs(60), 10);
+        let app = test::init_service(App::new().route("/", web::get().to(cache)).app_data(Data::new(repo))).await;
+        let res = test::TestRequest::get().uri("/").send_request(&app).await;
+        assert!(res.status().is_success());
+        let stats: CacheStats = test::read_body_json(res).await;
+        assert_eq!(stats, CacheStats { hits: 0, misses: 0, entries: 0 });
+    }
+}
@@ -1,28 +1,61 @@
 mod health;
+mod problem;
 mod user;
+mod webhook;
 
-use crate::api::health::health;
-use crate::api::user::{create_user, user_service};
+use crate::api::health::{cache, health};
+use crate::api::user::user_service;
+use crate::api::user::cursor::CursorKey;
+pub use crate::api::user::event_stream::EventStream;
+use crate::api::webhook::webhook_service;
+use crate::domain::clock::Clock;
+use crate::domain::entities::WebhookRules;
+use crate::domain::events::EventPublisher;
+use crate::domain::rules::UserRules;
+use crate::repository::cache::CachedRepository;
 use crate::repository::user::Repository;
-use crate::{PostgresRepository};
+use crate::repository::webhook::WebhookRepository;
 use actix_web::middleware::Logger;
 use actix_web::web::Data;
 use actix_web::{web, App, HttpServer};
 use actix_web_opentelemetry::RequestTracing;
-use std::ptr::replace;
 use std::sync::Arc;
 
-pub async fn serve(
-    url: &str,
-    repo: PostgresRepository,
-) -> std::io::Result<()> {
-    let repo = Data::new(repo);
+/// What the handlers are given, built by `main`.
+pub struct Services {
+    pub repo: Arc<dyn Repository>,
+    pub webhooks: Arc<dyn WebhookRepository>,
+    pub events: Arc<dyn EventPublisher>,
+    pub event_stream: Arc<EventStream>,
+    pub cache_stats: Option<Arc<CachedRepository>>,
+    pub rules: UserRules,
+    pub webhook_rules: WebhookRules,
+    pub cursor_secret: String,
+}
+
+pub async fn serve(url: &str, services: Services) -> std::io::Result<()> {
+    let repo: Data<dyn Repository> = Data::from(services.repo);
+    let webhooks: Data<dyn WebhookRepository> = Data::from(services.webhooks);
+    let events: Data<dyn EventPublisher> = Data::from(services.events);
+    // the readers only need the clock the writers validate with
+    let clock: Data<dyn Clock> = Data::from(services.rules.clock.clone());
+    let rules = Data::new(services.rules);
+    let webhook_rules = Data::new(services.webhook_rules);
+    let cursor_key = Data::new(CursorKey::new(&services.cursor_secret));
+    let event_stream: Data<EventStream> = Data::from(services.event_stream);
+    let cache_stats: Option<Data<CachedRepository>> = services.cache_stats.map(Data::from);
     HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
            // .wrap(RequestTracing::new())
-            .service(web::scope("/health").route("", web::get().to(health)))
-            .service(user_service(&repo))
+            .service(web::scope("/health")
+                .route("", web::get().to(health))
+                .route("/cache", web::get().to(cache))
+                .configure(|config| if let Some(cache_stats) = &cache_stats {
+                    config.app_data(cache_stats.clone());
+                }))
+            .service(user_service(&repo, &clock, &events, &rules, &cursor_key, &event_stream))
+            .service(webhook_service(&webhooks, &webhook_rules))
     })
     .bind((url, 8080))?
     .run()
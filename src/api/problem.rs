@@ -0,0 +1,280 @@
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use crate::domain::{create_user, create_webhook, delete_user, delete_webhook, get_user, list_users, list_webhook_deliveries, list_webhooks, patch_user, restore_user, update_user, user_history};
+use crate::domain::entities::ValidationErrors;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// RFC 7807 error body shared by every endpoint. `code` is a stable machine
+/// readable identifier, `type` points to its documentation.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub code: String,
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &str) -> Self {
+        Problem {
+            kind: format!("/problems/{}", code),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: None,
+            code: code.to_string(),
+            extensions: Map::new(),
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn extension(mut self, key: &str, value: impl Serialize) -> Self {
+        self.extensions.insert(key.to_string(), serde_json::to_value(value).unwrap());
+        self
+    }
+
+    pub fn response(&self) -> HttpResponse {
+        HttpResponse::build(StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+            .content_type(PROBLEM_JSON)
+            .body(serde_json::to_string(self).unwrap())
+    }
+
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        Problem::new(StatusCode::BAD_REQUEST, "bad_request").detail(detail)
+    }
+
+    /// 400 listing every refused field as `{"field": ..., "code": ...}` under `errors`.
+    pub fn validation(errors: ValidationErrors) -> Self {
+        let errors: Vec<Value> = errors.0.iter()
+            .map(|error| json!({ "field": error.field, "code": error.code }))
+            .collect();
+        Problem::new(StatusCode::BAD_REQUEST, "validation_failed")
+            .detail("one or more fields are invalid")
+            .extension("errors", errors)
+    }
+
+    pub fn not_found() -> Self {
+        Problem::new(StatusCode::NOT_FOUND, "user_not_found").detail("no user exists with this id")
+    }
+
+    pub fn webhook_not_found() -> Self {
+        Problem::new(StatusCode::NOT_FOUND, "webhook_not_found").detail("no webhook exists with this id")
+    }
+
+    pub fn version_mismatch() -> Self {
+        Problem::new(StatusCode::PRECONDITION_FAILED, "version_mismatch")
+            .detail("the user was modified since the version given in If-Match")
+    }
+
+    pub fn unknown() -> Self {
+        Problem::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+    }
+
+    fn operation(status: StatusCode, code: &str, error: patch_user::OperationError) -> Self {
+        Problem::new(status, code)
+            .detail(error.reason)
+            .extension("index", error.index)
+            .extension("path", error.path)
+    }
+}
+
+impl From<Problem> for HttpResponse {
+    fn from(problem: Problem) -> Self {
+        problem.response()
+    }
+}
+
+/// Error handler for the json and query extractors so malformed input is reported like any other problem.
+pub fn extractor_error<E: std::fmt::Display + std::fmt::Debug + 'static>(err: E) -> actix_web::Error {
+    let response = Problem::bad_request(err.to_string()).response();
+    InternalError::from_response(err, response).into()
+}
+
+impl From<create_user::Error> for Problem {
+    fn from(error: create_user::Error) -> Self {
+        match error {
+            create_user::Error::BadRequest(errors) => Problem::validation(errors),
+            create_user::Error::Conflict => Problem::new(StatusCode::CONFLICT, "user_conflict")
+                .detail("a user with this id already exists"),
+            create_user::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<get_user::Error> for Problem {
+    fn from(error: get_user::Error) -> Self {
+        match error {
+            get_user::Error::BadRequest(errors) => Problem::validation(errors),
+            get_user::Error::NotFound => Problem::not_found(),
+            get_user::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<list_users::Error> for Problem {
+    fn from(error: list_users::Error) -> Self {
+        match error {
+            list_users::Error::BadRequest => Problem::bad_request("the listing parameters are invalid"),
+            list_users::Error::NotFound => Problem::not_found(),
+            list_users::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<update_user::Error> for Problem {
+    fn from(error: update_user::Error) -> Self {
+        match error {
+            update_user::Error::BadRequest(errors) => Problem::validation(errors),
+            update_user::Error::Conflict => Problem::new(StatusCode::CONFLICT, "user_conflict"),
+            update_user::Error::NotFound => Problem::not_found(),
+            update_user::Error::VersionMismatch => Problem::version_mismatch(),
+            update_user::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<patch_user::Error> for Problem {
+    fn from(error: patch_user::Error) -> Self {
+        match error {
+            patch_user::Error::BadRequest(errors) => Problem::validation(errors),
+            patch_user::Error::NotFound => Problem::not_found(),
+            patch_user::Error::VersionMismatch => Problem::version_mismatch(),
+            patch_user::Error::Unknown => Problem::unknown(),
+            patch_user::Error::TestFailed(error) => Problem::operation(StatusCode::CONFLICT, "test_failed", error),
+            patch_user::Error::Unprocessable(error) => Problem::operation(StatusCode::UNPROCESSABLE_ENTITY, "unprocessable_operation", error),
+        }
+    }
+}
+
+impl From<delete_user::Error> for Problem {
+    fn from(error: delete_user::Error) -> Self {
+        match error {
+            delete_user::Error::BadRequest(errors) => Problem::validation(errors),
+            delete_user::Error::NotFound => Problem::not_found(),
+            delete_user::Error::VersionMismatch => Problem::version_mismatch(),
+            delete_user::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<restore_user::Error> for Problem {
+    fn from(error: restore_user::Error) -> Self {
+        match error {
+            restore_user::Error::BadRequest(errors) => Problem::validation(errors),
+            restore_user::Error::NotFound => Problem::not_found(),
+            restore_user::Error::NotDeleted => Problem::new(StatusCode::CONFLICT, "user_not_deleted")
+                .detail("the user is not deleted"),
+            restore_user::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<user_history::Error> for Problem {
+    fn from(error: user_history::Error) -> Self {
+        match error {
+            user_history::Error::BadRequest(errors) => Problem::validation(errors),
+            user_history::Error::NotFound => Problem::not_found(),
+            user_history::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<create_webhook::Error> for Problem {
+    fn from(error: create_webhook::Error) -> Self {
+        match error {
+            create_webhook::Error::BadRequest(errors) => Problem::validation(errors),
+            create_webhook::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<list_webhooks::Error> for Problem {
+    fn from(error: list_webhooks::Error) -> Self {
+        match error {
+            list_webhooks::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<delete_webhook::Error> for Problem {
+    fn from(error: delete_webhook::Error) -> Self {
+        match error {
+            delete_webhook::Error::BadRequest(errors) => Problem::validation(errors),
+            delete_webhook::Error::NotFound => Problem::webhook_not_found(),
+            delete_webhook::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+impl From<list_webhook_deliveries::Error> for Problem {
+    fn from(error: list_webhook_deliveries::Error) -> Self {
+        match error {
+            list_webhook_deliveries::Error::BadRequest(errors) => Problem::validation(errors),
+            list_webhook_deliveries::Error::NotFound => Problem::webhook_not_found(),
+            list_webhook_deliveries::Error::Unknown => Problem::unknown(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::to_bytes;
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::http::StatusCode;
+    use serde_json::json;
+    use crate::api::problem::{Problem, PROBLEM_JSON};
+    use crate::domain::{create_user, patch_user};
+    use crate::domain::entities::{FieldError, ValidationErrors};
+
+    #[test]
+    fn unknown_maps_to_internal_error() {
+        let problem = Problem::from(create_user::Error::Unknown);
+        assert_eq!(problem.status, 500);
+        assert_eq!(problem.code, "internal_error");
+    }
+
+    #[test]
+    fn validation_lists_every_field() {
+        let errors = ValidationErrors(vec![
+            FieldError { field: "first_name", code: "empty" },
+            FieldError { field: "birthday_date", code: "invalid_format" },
+        ]);
+        let problem = Problem::from(create_user::Error::BadRequest(errors));
+        assert_eq!(problem.status, 400);
+        assert_eq!(problem.code, "validation_failed");
+        assert_eq!(problem.extensions["errors"], json!([
+            {"field": "first_name", "code": "empty"},
+            {"field": "birthday_date", "code": "invalid_format"},
+        ]));
+    }
+
+    #[actix_web::test]
+    async fn response_is_problem_json() {
+        let error = patch_user::OperationError { index: Some(1), path: "/city".to_string(), reason: "test failed" };
+        let res = Problem::from(patch_user::Error::TestFailed(error)).response();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        assert_eq!(res.headers().get(CONTENT_TYPE).unwrap(), PROBLEM_JSON);
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(res.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body, json!({
+            "type": "/problems/test_failed",
+            "title": "Conflict",
+            "status": 409,
+            "detail": "test failed",
+            "code": "test_failed",
+            "index": 1,
+            "path": "/city",
+        }));
+    }
+}
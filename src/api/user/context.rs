@@ -0,0 +1,44 @@
+use actix_web::HttpRequest;
+use uuid::Uuid;
+use crate::repository::history::ChangeContext;
+
+pub const ACTOR: &str = "x-actor";
+pub const REQUEST_ID: &str = "x-request-id";
+
+/// Who is making the change, from `X-Actor` as set by the gateway, and the
+/// `X-Request-Id` tying it to the logs, generated when the client sent none.
+pub fn change_context(http_req: &HttpRequest) -> ChangeContext {
+    let header = |name: &str| http_req.headers().get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string);
+    ChangeContext {
+        actor: header(ACTOR).unwrap_or_else(|| "anonymous".to_string()),
+        request_id: header(REQUEST_ID).unwrap_or_else(|| Uuid::new_v4().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use crate::api::user::context::{change_context, ACTOR, REQUEST_ID};
+
+    #[test]
+    fn change_context_reads_headers() {
+        let req = TestRequest::default()
+            .insert_header((ACTOR, "support@example.com"))
+            .insert_header((REQUEST_ID, "req-42"))
+            .to_http_request();
+        let context = change_context(&req);
+        assert_eq!(context.actor, "support@example.com");
+        assert_eq!(context.request_id, "req-42");
+    }
+
+    #[test]
+    fn change_context_defaults() {
+        let context = change_context(&TestRequest::default().to_http_request());
+        assert_eq!(context.actor, "anonymous");
+        assert!(!context.request_id.is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::repository::user::KeysetCursor;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signs and verifies the opaque `after` tokens handed out by `GET /user`,
+/// so clients cannot forge a position in the keyset.
+#[derive(Clone)]
+pub struct CursorKey {
+    secret: Vec<u8>,
+}
+
+impl CursorKey {
+    pub fn new(secret: &str) -> Self {
+        Self { secret: secret.as_bytes().to_vec() }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("hmac accepts keys of any size")
+    }
+
+    pub fn encode(&self, cursor: &KeysetCursor) -> String {
+        let payload = serde_json::to_vec(cursor).unwrap();
+        let mut mac = self.mac();
+        mac.update(&payload);
+        let signature = mac.finalize().into_bytes();
+        format!("{}.{}",
+                base64::encode_config(&payload, base64::URL_SAFE_NO_PAD),
+                base64::encode_config(signature, base64::URL_SAFE_NO_PAD))
+    }
+
+    pub fn decode(&self, token: &str) -> Result<KeysetCursor, ()> {
+        let (payload, signature) = token.split_once('.').ok_or(())?;
+        let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).map_err(|_| ())?;
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD).map_err(|_| ())?;
+        let mut mac = self.mac();
+        mac.update(&payload);
+        mac.verify_slice(&signature).map_err(|_| ())?;
+        serde_json::from_slice(&payload).map_err(|_| ())
+    }
+}
+
+#[cfg(test)]
+impl CursorKey {
+    pub fn test() -> Self {
+        Self::new("test-secret")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::user::cursor::CursorKey;
+    use uuid::uuid;
+    use crate::repository::user::{KeysetCursor, SortField, SortOrder};
+
+    fn cursor() -> KeysetCursor {
+        KeysetCursor {
+            sort: SortField::City,
+            order: SortOrder::Asc,
+            value: "nice".to_string(),
+            id: uuid!("0a708f88-bedb-4dad-a2f2-65dd4e8c132a"),
+        }
+    }
+
+    #[test]
+    fn round_trip_works() {
+        let key = CursorKey::test();
+        let token = key.encode(&cursor());
+        assert_eq!(key.decode(&token), Ok(cursor()));
+    }
+
+    #[test]
+    fn tampered_token_fails() {
+        let key = CursorKey::test();
+        let token = key.encode(&cursor());
+        let (_, signature) = token.split_once('.').unwrap();
+        let mut forged = cursor();
+        forged.value = "paris".to_string();
+        let payload = base64::encode_config(serde_json::to_vec(&forged).unwrap(), base64::URL_SAFE_NO_PAD);
+        assert_eq!(key.decode(&format!("{}.{}", payload, signature)), Err(()));
+        assert_eq!(CursorKey::new("other-secret").decode(&token), Err(()));
+        assert_eq!(key.decode("garbage"), Err(()));
+    }
+}
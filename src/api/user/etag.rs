@@ -0,0 +1,114 @@
+use std::time::SystemTime;
+use actix_web::http::header::{CacheControl, CacheDirective, ETag, EntityTag, Header, HttpDate, IfMatch, IfNoneMatch, LastModified, IF_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse};
+use crate::api::problem::Problem;
+use sha2::{Digest, Sha256};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+
+/// Strong entity tag for a user on `today`, as its age and next birthday change with the day.
+/// Every response carrying the user uses it, `If-Match` only looks at the version in front.
+pub fn etag(version: i64, today: NaiveDate) -> ETag {
+    ETag(EntityTag::new_strong(format!("{}-{}", version, today)))
+}
+
+/// Strong entity tag for a user read from its history at `at`, whose age is the one it had then.
+/// Never equal to the tag of a current read, even for the same version.
+pub fn as_of_etag(version: i64, at: DateTime<Utc>) -> ETag {
+    ETag(EntityTag::new_strong(format!("{}-at-{}", version, at.to_rfc3339_opts(SecondsFormat::Micros, true))))
+}
+
+/// Strong entity tag for a representation that has no single version, like a page of users.
+pub fn body_etag(body: &[u8]) -> ETag {
+    ETag(EntityTag::new_strong(format!("{:x}", Sha256::digest(body))))
+}
+
+/// `Last-Modified` for a user last changed at `updated_at`, to the second as HTTP dates are.
+pub fn last_modified(updated_at: DateTime<Utc>) -> LastModified {
+    LastModified(HttpDate::from(SystemTime::from(updated_at)))
+}
+
+/// User data is private and clients must revalidate it with `If-None-Match` before reuse.
+pub fn cache_control() -> CacheControl {
+    CacheControl(vec![CacheDirective::Private, CacheDirective::NoCache])
+}
+
+/// Whether the client already holds the representation tagged `etag`.
+pub fn not_modified(req: &HttpRequest, etag: &ETag) -> bool {
+    match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag.0)),
+        Err(_) => false,
+    }
+}
+
+pub fn not_modified_response(etag: ETag) -> HttpResponse {
+    HttpResponse::NotModified()
+        .insert_header(etag)
+        .insert_header(cache_control())
+        .finish()
+}
+
+fn precondition_required() -> HttpResponse {
+    Problem::new(StatusCode::PRECONDITION_REQUIRED, "precondition_required")
+        .detail("an If-Match header with the user ETag is required")
+        .response()
+}
+
+/// Reads the version a client expects from `If-Match`, dated or not, answering
+/// `428 Precondition Required` when it is missing and
+/// `412 Precondition Failed` when it cannot match any version.
+#[allow(clippy::result_large_err)]
+pub fn if_match(req: &HttpRequest) -> Result<i64, HttpResponse> {
+    if !req.headers().contains_key(IF_MATCH) {
+        return Err(precondition_required());
+    }
+    match IfMatch::parse(req) {
+        Ok(IfMatch::Items(tags)) => tags.iter()
+            .filter(|tag| !tag.weak)
+            .find_map(|tag| tag.tag().split('-').next().and_then(|version| version.parse::<i64>().ok()))
+            .ok_or_else(|| Problem::version_mismatch().response()),
+        Ok(IfMatch::Any) => Err(precondition_required()),
+        Err(_) => Err(Problem::version_mismatch().response()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{IF_MATCH, IF_NONE_MATCH};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use chrono::{NaiveDate, Utc};
+    use crate::api::user::etag::{as_of_etag, etag, if_match, not_modified};
+
+    #[test]
+    fn if_match_works() {
+        let req = TestRequest::default().insert_header((IF_MATCH, "\"3\"")).to_http_request();
+        assert_eq!(if_match(&req).unwrap(), 3);
+        let dated = TestRequest::default().insert_header((IF_MATCH, etag(3, NaiveDate::from_ymd_opt(2022, 10, 3).unwrap()).0.to_string())).to_http_request();
+        assert_eq!(if_match(&dated).unwrap(), 3);
+        let as_of = TestRequest::default().insert_header((IF_MATCH, as_of_etag(3, Utc::now()).0.to_string())).to_http_request();
+        assert_eq!(if_match(&as_of).unwrap(), 3);
+    }
+
+    #[test]
+    fn if_match_fails() {
+        let missing = TestRequest::default().to_http_request();
+        let weak = TestRequest::default().insert_header((IF_MATCH, "W/\"3\"")).to_http_request();
+        let any = TestRequest::default().insert_header((IF_MATCH, "*")).to_http_request();
+        assert_eq!(if_match(&missing).err().unwrap().status(), StatusCode::PRECONDITION_REQUIRED);
+        assert_eq!(if_match(&weak).err().unwrap().status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(if_match(&any).err().unwrap().status(), StatusCode::PRECONDITION_REQUIRED);
+    }
+
+    #[test]
+    fn not_modified_works() {
+        let today = NaiveDate::from_ymd_opt(2022, 10, 3).unwrap();
+        let same = TestRequest::default().insert_header((IF_NONE_MATCH, "\"2-2022-10-03\", W/\"3-2022-10-03\"")).to_http_request();
+        let other = TestRequest::default().insert_header((IF_NONE_MATCH, "\"2-2022-10-03\"")).to_http_request();
+        let missing = TestRequest::default().to_http_request();
+        assert!(not_modified(&same, &etag(3, today)));
+        assert!(!not_modified(&other, &etag(3, today)));
+        assert!(!not_modified(&missing, &etag(3, today)));
+    }
+}
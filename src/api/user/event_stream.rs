@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use actix_web::web::{Bytes, Data};
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{interval_at, Instant, Interval};
+use uuid::Uuid;
+use crate::domain::events::{Subscriber, UserEvent};
+
+pub const LAST_EVENT_ID: &str = "Last-Event-ID";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamEvent {
+    pub id: u64,
+    pub event: UserEvent,
+}
+
+/// What the open connections receive, an event or the order to reset.
+#[derive(Debug, Clone)]
+enum Broadcast {
+    Event(StreamEvent),
+    Reset,
+}
+
+struct Replay {
+    next_id: u64,
+    buffer: VecDeque<StreamEvent>,
+}
+
+/// Numbers the published events and fans them out to the open `/user/events` connections,
+/// keeping the last `capacity` of them so a reconnecting client can resume from `Last-Event-ID`.
+/// Ids are `{generation}-{number}`: a client coming back to another replica, or after a restart,
+/// sends an id of another generation and is told to reload rather than given gaps or duplicates.
+pub struct EventStream {
+    generation: String,
+    replay: Mutex<Replay>,
+    sender: broadcast::Sender<Broadcast>,
+    capacity: usize,
+    /// Comment sent on idle connections so proxies do not close them.
+    pub heartbeat: Duration,
+}
+
+impl EventStream {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        EventStream {
+            generation: Uuid::new_v4().simple().to_string(),
+            replay: Mutex::new(Replay { next_id: 1, buffer: VecDeque::with_capacity(capacity) }),
+            sender,
+            capacity,
+            heartbeat: Duration::from_secs(15),
+        }
+    }
+
+    /// The number in an id this stream gave, `None` for any other.
+    fn sequence(&self, id: &str) -> Option<u64> {
+        id.trim().strip_prefix(self.generation.as_str())?.strip_prefix('-')?.parse().ok()
+    }
+
+    /// The buffered events after `last_event_id` and a receiver for the next ones, without gap nor duplicate.
+    /// `None` instead of the events when some of them were already dropped from the buffer
+    /// or the id was not given by this stream.
+    fn subscribe(&self, last_event_id: Option<&str>) -> (Option<Vec<StreamEvent>>, broadcast::Receiver<Broadcast>) {
+        let replay = self.replay.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let missed = match last_event_id.map(|id| self.sequence(id)) {
+            None => Some(vec![]),
+            // from another replica or a previous run
+            Some(None) => None,
+            Some(Some(last)) if last >= replay.next_id => None,
+            Some(Some(last)) => {
+                let oldest = replay.buffer.front().map_or(replay.next_id, |event| event.id);
+                if last + 1 < oldest {
+                    None
+                } else {
+                    Some(replay.buffer.iter().filter(|event| event.id > last).cloned().collect())
+                }
+            }
+        };
+        (missed, receiver)
+    }
+}
+
+impl Subscriber for EventStream {
+    fn handle(&self, event: &UserEvent) {
+        let mut replay = self.replay.lock().unwrap();
+        let event = StreamEvent { id: replay.next_id, event: event.clone() };
+        replay.next_id += 1;
+        if self.capacity > 0 {
+            if replay.buffer.len() == self.capacity {
+                replay.buffer.pop_front();
+            }
+            replay.buffer.push_back(event.clone());
+        }
+        // sent under the lock so connections see the events in buffer order, none listening is fine
+        let _ = self.sender.send(Broadcast::Event(event));
+    }
+
+    /// Events may have been lost: open connections are reset, and so is any client resuming from before.
+    fn resync(&self) {
+        let mut replay = self.replay.lock().unwrap();
+        replay.buffer.clear();
+        // skipping an id makes every earlier one look dropped from the buffer
+        replay.next_id += 1;
+        let _ = self.sender.send(Broadcast::Reset);
+    }
+}
+
+/// Whether the event concerns a user living in `city`, or who just left it.
+fn in_city(event: &UserEvent, city: &str) -> bool {
+    match event {
+        UserEvent::UserCreated(event) => event.city == city,
+        UserEvent::UserUpdated(event) => event.city == city
+            || event.changes.iter().any(|change| change.field == "city" && change.old == city),
+        UserEvent::UserDeleted(event) => event.city == city,
+        UserEvent::UserRestored(event) => event.city == city,
+    }
+}
+
+fn frame(generation: &str, event: &StreamEvent) -> Bytes {
+    Bytes::from(format!(
+        "id: {}-{}\nevent: {}\ndata: {}\n\n",
+        generation,
+        event.id,
+        event.event.event_type(),
+        serde_json::to_string(&event.event).unwrap(),
+    ))
+}
+
+/// Tells the client events were lost and it should reload the users before using the stream.
+fn reset() -> Bytes {
+    Bytes::from_static(b"event: reset\ndata: {}\n\n")
+}
+
+fn heartbeat() -> Bytes {
+    Bytes::from_static(b": heartbeat\n\n")
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Params {
+    pub city: Option<String>,
+}
+
+struct Connection {
+    generation: String,
+    pending: VecDeque<Bytes>,
+    receiver: broadcast::Receiver<Broadcast>,
+    heartbeat: Interval,
+    city: Option<String>,
+}
+
+impl Connection {
+    async fn next(&mut self) -> Option<Bytes> {
+        if let Some(frame) = self.pending.pop_front() {
+            return Some(frame);
+        }
+        loop {
+            tokio::select! {
+                received = self.receiver.recv() => match received {
+                    Ok(Broadcast::Event(event)) if self.city.as_deref().is_none_or(|city| in_city(&event.event, city)) => return Some(frame(&self.generation, &event)),
+                    Ok(Broadcast::Event(_)) => continue,
+                    Ok(Broadcast::Reset) => return Some(reset()),
+                    // too slow to keep up, the channel dropped events for it
+                    Err(RecvError::Lagged(_)) => return Some(reset()),
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = self.heartbeat.tick() => return Some(heartbeat()),
+            }
+        }
+    }
+}
+//#[tracing::instrument]
+pub async fn serve(stream: Data<EventStream>, params: web::Query<Params>, http_req: HttpRequest) -> impl Responder {
+    let last_event_id = http_req.headers().get(LAST_EVENT_ID)
+        .map(|value| value.to_str().unwrap_or_default());
+    let city = params.into_inner().city;
+    let (missed, receiver) = stream.subscribe(last_event_id);
+    let pending = match missed {
+        Some(events) => events.iter()
+            .filter(|event| city.as_deref().is_none_or(|city| in_city(&event.event, city)))
+            .map(|event| frame(&stream.generation, event))
+            .collect(),
+        None => VecDeque::from(vec![reset()]),
+    };
+    let connection = Connection {
+        generation: stream.generation.clone(),
+        pending,
+        receiver,
+        heartbeat: interval_at(Instant::now() + stream.heartbeat, stream.heartbeat),
+        city,
+    };
+    let body = futures::stream::unfold(connection, |mut connection| async move {
+        connection.next().await.map(|frame| (Ok::<_, actix_web::Error>(frame), connection))
+    });
+    HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, "text/event-stream"))
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        // nginx buffers responses unless told otherwise
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use actix_web::{App, web};
+    use actix_web::test::{init_service, TestRequest};
+    use actix_web::body::MessageBody;
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::web::Data;
+    use chrono::{NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::api::user::event_stream::{in_city, serve, EventStream, LAST_EVENT_ID};
+    use crate::domain::events::{Subscriber, UserEvent};
+    use crate::repository::history::ChangeContext;
+    use crate::repository::user::DbUser;
+
+    fn user(city: &str) -> DbUser {
+        DbUser {
+            id: Uuid::new_v4(),
+            first_name: "Hugo".to_string(),
+            last_name: "Muf".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            city: city.to_string(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+
+    async fn next_frame<B: MessageBody + Unpin>(body: &mut B) -> String where B::Error: std::fmt::Debug {
+        let chunk = poll_fn(|cx| Pin::new(&mut *body).poll_next(cx)).await.unwrap().unwrap();
+        String::from_utf8(chunk.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn in_city_follows_users_leaving_it() {
+        let old = user("Nice");
+        let moved = UserEvent::updated(&old, &DbUser { city: "Paris".to_string(), version: 2, ..old.clone() }, &ChangeContext::test());
+        assert!(in_city(&moved, "Nice"));
+        assert!(in_city(&moved, "Paris"));
+        assert!(!in_city(&moved, "Lyon"));
+        assert!(in_city(&UserEvent::deleted(old.id, "Nice", 2, Utc::now(), &ChangeContext::test()), "Nice"));
+    }
+
+    #[actix_web::test]
+    async fn test_user_events_route_streams_filtered_events() {
+        let mut stream = EventStream::new(10);
+        stream.heartbeat = Duration::from_millis(50);
+        let stream = Arc::new(stream);
+        let app = init_service(
+            App::new()
+                .route("/events", web::get().to(serve))
+                .app_data(Data::from(stream.clone()))).await;
+        let res = TestRequest::get().uri("/events?city=Nice").send_request(&app).await;
+        assert_eq!(res.headers().get(CONTENT_TYPE).unwrap(), "text/event-stream");
+        let mut body = res.into_body();
+
+        stream.handle(&UserEvent::created(&user("Paris"), &ChangeContext::test()));
+        let nice = user("Nice");
+        stream.handle(&UserEvent::created(&nice, &ChangeContext::test()));
+        let frame = next_frame(&mut body).await;
+        assert!(frame.starts_with(&format!("id: {}-2\nevent: UserCreated\ndata: {{", stream.generation)), "{}", frame);
+        assert!(frame.contains(&nice.id.to_string()));
+        assert_eq!(next_frame(&mut body).await, ": heartbeat\n\n");
+    }
+
+    #[actix_web::test]
+    async fn test_user_events_route_resumes_from_last_event_id() {
+        let stream = Arc::new(EventStream::new(2));
+        let app = init_service(
+            App::new()
+                .route("/events", web::get().to(serve))
+                .app_data(Data::from(stream.clone()))).await;
+        for _ in 0..3 {
+            stream.handle(&UserEvent::created(&user("Nice"), &ChangeContext::test()));
+        }
+        let id = |number: u64| format!("{}-{}", stream.generation, number);
+        let res = TestRequest::get().uri("/events").insert_header((LAST_EVENT_ID, id(1))).send_request(&app).await;
+        let mut body = res.into_body();
+        assert!(next_frame(&mut body).await.starts_with(&format!("id: {}\n", id(2))));
+        assert!(next_frame(&mut body).await.starts_with(&format!("id: {}\n", id(3))));
+        stream.handle(&UserEvent::created(&user("Nice"), &ChangeContext::test()));
+        assert!(next_frame(&mut body).await.starts_with(&format!("id: {}\n", id(4))));
+
+        // 2 was dropped from the buffer, 9 was never given, the others come from another replica or run
+        let other = EventStream::new(2);
+        for last_event_id in [id(1), id(9), format!("{}-3", other.generation), "3".to_string()] {
+            let res = TestRequest::get().uri("/events").insert_header((LAST_EVENT_ID, last_event_id)).send_request(&app).await;
+            let mut body = res.into_body();
+            assert_eq!(next_frame(&mut body).await, "event: reset\ndata: {}\n\n");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_user_events_route_resets_on_resync() {
+        let stream = Arc::new(EventStream::new(10));
+        let app = init_service(
+            App::new()
+                .route("/events", web::get().to(serve))
+                .app_data(Data::from(stream.clone()))).await;
+        stream.handle(&UserEvent::created(&user("Nice"), &ChangeContext::test()));
+        let res = TestRequest::get().uri("/events").send_request(&app).await;
+        let mut body = res.into_body();
+
+        stream.resync();
+        assert_eq!(next_frame(&mut body).await, "event: reset\ndata: {}\n\n");
+        stream.handle(&UserEvent::created(&user("Nice"), &ChangeContext::test()));
+        let next = format!("{}-3", stream.generation);
+        assert!(next_frame(&mut body).await.starts_with(&format!("id: {}\n", next)));
+
+        // a client that heard 1 missed what the change feed lost, one that heard 3 did not
+        let res = TestRequest::get().uri("/events").insert_header((LAST_EVENT_ID, format!("{}-1", stream.generation))).send_request(&app).await;
+        assert_eq!(next_frame(&mut res.into_body()).await, "event: reset\ndata: {}\n\n");
+        let (missed, _) = stream.subscribe(Some(&next));
+        assert_eq!(missed, Some(vec![]));
+    }
+}
@@ -1,41 +1,262 @@
-use actix_web::{HttpResponse, Responder, web};
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
 use actix_web::web::Data;
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
 
-use crate::PostgresRepository;
+use crate::repository::user::Repository;
 use serde::Deserialize;
 use serde::Serialize;
+use crate::api::user::cursor::CursorKey;
+use crate::api::user::etag::{body_etag, cache_control, last_modified, not_modified, not_modified_response};
+use crate::api::problem::Problem;
+use crate::domain::clock::Clock;
 use crate::domain::list_users;
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Params {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub city: Option<String>,
+    pub last_name: Option<String>,
+    pub birthday_from: Option<String>,
+    pub birthday_to: Option<String>,
+    pub updated_since: Option<String>,
+    pub include_deleted: Option<bool>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub after: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Response {
-    pub id: String,
+    pub id: Uuid,
     pub first_name: String,
     pub last_name: String,
     pub birthday_date: NaiveDate,
     pub city: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub age: u32,
+    pub next_birthday: NaiveDate,
 }
-//#[tracing::instrument]
-pub async fn serve(repo: Data<PostgresRepository>) -> impl Responder {
 
-    match list_users::execute(repo).await {
-        Ok(users) => {
-            let res:Vec<Response> = users.into_iter().map(|x| {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageResponse {
+    pub items: Vec<Response>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub next_offset: Option<i64>,
+    pub next_cursor: Option<String>,
+}
+//#[tracing::instrument]
+pub async fn serve(repo: Data<dyn Repository>, clock: Data<dyn Clock>, key: Data<CursorKey>, params: web::Query<Params>, http_req: HttpRequest) -> impl Responder {
+    let params = params.into_inner();
+    let after = match params.after.as_deref().map(|token| key.decode(token)) {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(_)) => return Problem::bad_request("the after cursor is invalid").response(),
+        None => None,
+    };
+    let req = list_users::Request {
+        limit: params.limit,
+        offset: params.offset,
+        city: params.city,
+        last_name: params.last_name,
+        birthday_from: params.birthday_from,
+        birthday_to: params.birthday_to,
+        updated_since: params.updated_since,
+        include_deleted: params.include_deleted.unwrap_or(false),
+        sort: params.sort,
+        order: params.order,
+        after,
+    };
+    match list_users::execute(repo.get_ref(), clock.get_ref(), req).await {
+        Ok(page) => {
+            // an empty page has no date to give
+            let updated_at = page.users.iter().map(|user| user.updated_at).max();
+            let items:Vec<Response> = page.users.into_iter().map(|x| {
                 Response {
                     id: x.id,
                     first_name: x.first_name,
                     last_name: x.last_name,
                     birthday_date: x.birthday_date,
                     city: x.city,
+                    created_at: x.created_at,
+                    updated_at: x.updated_at,
+                    deleted_at: x.deleted_at,
+                    age: x.age,
+                    next_birthday: x.next_birthday,
                 }
             }
             ).collect();
-            HttpResponse::Created()
-                .content_type("application/json")
-                .body(serde_json::to_string(&res).unwrap())
+            let body = serde_json::to_string(&PageResponse {
+                items,
+                total: page.total,
+                limit: page.limit,
+                offset: page.offset,
+                next_offset: page.next_offset,
+                next_cursor: page.next_cursor.map(|cursor| key.encode(&cursor)),
+            }).unwrap();
+            let etag = body_etag(body.as_bytes());
+            if not_modified(&http_req, &etag) {
+                return not_modified_response(etag);
+            }
+            let mut response = HttpResponse::Ok();
+            response.content_type("application/json")
+                .insert_header(etag)
+                .insert_header(cache_control());
+            if let Some(updated_at) = updated_at {
+                response.insert_header(last_modified(updated_at));
+            }
+            response.body(body)
         } ,
-        Err(list_users::Error::BadRequest) => HttpResponse::BadRequest().finish(),
-        Err(list_users::Error::NotFound) => HttpResponse::NotFound().finish(),
-        Err(list_users::Error::Unknown) => HttpResponse::Conflict().finish(),
+        Err(error) => Problem::from(error).response(),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::history::ChangeContext;
+    use std::sync::Arc;
+    use actix_web::{App, test, web};
+    use actix_web::http::header::{ETAG, IF_NONE_MATCH, LAST_MODIFIED};
+    use actix_web::http::StatusCode;
+    use actix_web::web::Data;
+    use chrono::{NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::api::user::cursor::CursorKey;
+    use crate::api::user::etag::last_modified;
+    use crate::api::user::list_users::{PageResponse, serve};
+    use crate::domain::clock::{Clock, FixedClock, SystemClock};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::{DbUser, Repository};
+
+    #[actix_web::test]
+    async fn test_list_users_route_ok() {
+        let repository = InMemoryRepository::new();
+        for city in ["nice", "paris", "nice"] {
+            repository.insert(DbUser {
+                id: Uuid::new_v4(),
+                last_name: "muf".to_string(),
+                first_name: "hugo".to_string(),
+                city: city.to_string(),
+                birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+                version: 1,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                deleted_at: None,
+            }, &ChangeContext::test()).await.unwrap();
+        }
+        let repo: Data<dyn Repository> = Data::from(Arc::new(repository) as Arc<dyn Repository>);
+        let app = test::init_service(
+            App::new()
+                .route("/", web::get().to(serve))
+                .app_data(repo)
+                .app_data(Data::from(Arc::new(SystemClock) as Arc<dyn Clock>))
+                .app_data(Data::new(CursorKey::test()))).await;
+        let res = test::TestRequest::get().uri("/?city=nice&limit=1").send_request(&app).await;
+        assert!(res.status().is_success());
+        let result: PageResponse = test::read_body_json(res).await;
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(result.items[0].city, "nice");
+        assert_eq!(result.total, 2);
+        assert_eq!(result.next_offset, Some(1));
+
+        let token = result.next_cursor.unwrap();
+        let res = test::TestRequest::get().uri(&format!("/?city=nice&limit=1&after={}", token)).send_request(&app).await;
+        let next: PageResponse = test::read_body_json(res).await;
+        assert_eq!(next.items.len(), 1);
+        assert_ne!(next.items[0].id, result.items[0].id);
+        assert_eq!(next.next_cursor, None);
+    }
+
+    #[actix_web::test]
+    async fn test_list_users_route_not_modified() {
+        let repository = InMemoryRepository::new();
+        let repo: Data<dyn Repository> = Data::from(Arc::new(repository) as Arc<dyn Repository>);
+        let app = test::init_service(
+            App::new()
+                .route("/", web::get().to(serve))
+                .app_data(repo.clone())
+                .app_data(Data::from(Arc::new(SystemClock) as Arc<dyn Clock>))
+                .app_data(Data::new(CursorKey::test()))).await;
+        let res = test::TestRequest::get().uri("/").send_request(&app).await;
+        assert!(!res.headers().contains_key(LAST_MODIFIED));
+        let etag = res.headers().get(ETAG).unwrap().clone();
+        let res = test::TestRequest::get().uri("/").insert_header((IF_NONE_MATCH, etag.clone())).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let updated_at = repo.insert(DbUser {
+            id: Uuid::new_v4(),
+            last_name: "muf".to_string(),
+            first_name: "hugo".to_string(),
+            city: "nice".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap().updated_at;
+        let res = test::TestRequest::get().uri("/").insert_header((IF_NONE_MATCH, etag)).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(LAST_MODIFIED).unwrap(), last_modified(updated_at).to_string().as_str());
+    }
+
+    #[actix_web::test]
+    async fn test_list_users_route_etag_follows_the_day() {
+        let repository = InMemoryRepository::new();
+        repository.insert(DbUser {
+            id: Uuid::new_v4(),
+            last_name: "muf".to_string(),
+            first_name: "hugo".to_string(),
+            city: "nice".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap();
+        let repo: Data<dyn Repository> = Data::from(Arc::new(repository) as Arc<dyn Repository>);
+        let mut etags = vec![];
+        for today in [NaiveDate::from_ymd_opt(2022, 3, 13).unwrap(), NaiveDate::from_ymd_opt(2022, 3, 14).unwrap()] {
+            let app = test::init_service(
+                App::new()
+                    .route("/", web::get().to(serve))
+                    .app_data(repo.clone())
+                    .app_data(Data::from(Arc::new(FixedClock(today)) as Arc<dyn Clock>))
+                    .app_data(Data::new(CursorKey::test()))).await;
+            let res = test::TestRequest::get().uri("/").send_request(&app).await;
+            etags.push(res.headers().get(ETAG).unwrap().clone());
+        }
+        // the user turned 7, the page hash changes with the ages it holds
+        assert_ne!(etags[0], etags[1]);
+    }
+
+    #[actix_web::test]
+    async fn test_list_users_route_fail_forged_cursor() {
+        let repo: Data<dyn Repository> = Data::from(Arc::new(InMemoryRepository::new()) as Arc<dyn Repository>);
+        let app = test::init_service(
+            App::new()
+                .route("/", web::get().to(serve))
+                .app_data(repo)
+                .app_data(Data::from(Arc::new(SystemClock) as Arc<dyn Clock>))
+                .app_data(Data::new(CursorKey::test()))).await;
+        let res = test::TestRequest::get().uri("/?after=eyJpZCI6ImEifQ.AAAA").send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_list_users_route_fail_bad_request() {
+        let repo: Data<dyn Repository> = Data::from(Arc::new(InMemoryRepository::new()) as Arc<dyn Repository>);
+        let app = test::init_service(
+            App::new()
+                .route("/", web::get().to(serve))
+                .app_data(repo)
+                .app_data(Data::from(Arc::new(SystemClock) as Arc<dyn Clock>))
+                .app_data(Data::new(CursorKey::test()))).await;
+        let res = test::TestRequest::get().uri("/?sort=password").send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,61 @@
+use actix_web::http::header::{AcceptLanguage, Header, Preference};
+use actix_web::{HttpRequest, web};
+use serde::Deserialize;
+use crate::api::problem::Problem;
+use crate::domain::entities::DateOrder;
+
+/// Regions writing numeric dates month first, `03/10/1994` is then March 10th.
+const MONTH_DAY_REGIONS: [&str; 2] = ["US", "PH"];
+
+#[derive(Debug, Default, Deserialize)]
+struct Params {
+    date_order: Option<String>,
+}
+
+/// Day/month order of numeric birthdays sent by the client: an explicit `?date_order=dmy|mdy`
+/// wins over the region of the preferred `Accept-Language`, `None` leaves the configured default.
+#[allow(clippy::result_large_err)]
+pub fn date_order(http_req: &HttpRequest) -> Result<Option<DateOrder>, Problem> {
+    let params = web::Query::<Params>::from_query(http_req.query_string())
+        .map(web::Query::into_inner)
+        .unwrap_or_default();
+    if let Some(order) = &params.date_order {
+        return order.parse()
+            .map(Some)
+            .map_err(|_| Problem::bad_request("date_order must be dmy or mdy"));
+    }
+    let region = match AcceptLanguage::parse(http_req).map(|header| header.preference()) {
+        Ok(Preference::Specific(tag)) => tag.region().map(str::to_uppercase),
+        _ => None,
+    };
+    Ok(region.map(|region| {
+        if MONTH_DAY_REGIONS.contains(&region.as_str()) { DateOrder::MonthDay } else { DateOrder::DayMonth }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::ACCEPT_LANGUAGE;
+    use actix_web::test::TestRequest;
+    use crate::api::user::locale::date_order;
+    use crate::domain::entities::DateOrder;
+
+    #[test]
+    fn date_order_from_accept_language() {
+        let req = TestRequest::default().insert_header((ACCEPT_LANGUAGE, "en-US,fr;q=0.5")).to_http_request();
+        assert_eq!(date_order(&req).unwrap(), Some(DateOrder::MonthDay));
+        let req = TestRequest::default().insert_header((ACCEPT_LANGUAGE, "fr-FR")).to_http_request();
+        assert_eq!(date_order(&req).unwrap(), Some(DateOrder::DayMonth));
+        let req = TestRequest::default().insert_header((ACCEPT_LANGUAGE, "en")).to_http_request();
+        assert_eq!(date_order(&req).unwrap(), None);
+        assert_eq!(date_order(&TestRequest::default().to_http_request()).unwrap(), None);
+    }
+
+    #[test]
+    fn explicit_date_order_wins() {
+        let req = TestRequest::with_uri("/?date_order=dmy").insert_header((ACCEPT_LANGUAGE, "en-US")).to_http_request();
+        assert_eq!(date_order(&req).unwrap(), Some(DateOrder::DayMonth));
+        let req = TestRequest::with_uri("/?date_order=ymd").to_http_request();
+        assert_eq!(date_order(&req).unwrap_err().status, 400);
+    }
+}
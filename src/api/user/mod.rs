@@ -1,8 +1,11 @@
-use std::sync::Arc;
-use actix_web::{App, Scope, web};
-use actix_web::dev::WebService;
-use actix_web::web::{Data, ServiceConfig};
-use crate::PostgresRepository;
+use actix_web::{Scope, web};
+use actix_web::web::Data;
+use crate::api::problem::extractor_error;
+use crate::api::user::cursor::CursorKey;
+use crate::api::user::event_stream::EventStream;
+use crate::domain::clock::Clock;
+use crate::domain::events::EventPublisher;
+use crate::domain::rules::UserRules;
 use crate::repository::user::Repository;
 
 pub mod create_user;
@@ -10,13 +13,33 @@ pub mod get_user;
 pub mod delete_user;
 pub mod list_users;
 mod update_user;
+mod patch_user;
+mod restore_user;
+mod user_history;
+pub mod cursor;
+pub mod locale;
+pub mod etag;
+pub mod context;
+pub mod event_stream;
 
-pub fn user_service(repo: &Data<PostgresRepository>) -> Scope {
+pub fn user_service(repo: &Data<dyn Repository>, clock: &Data<dyn Clock>, events: &Data<dyn EventPublisher>, rules: &Data<UserRules>, cursor_key: &Data<CursorKey>, event_stream: &Data<EventStream>) -> Scope {
     web::scope("/user")
         .route("", web::post().to(create_user::serve))
+        // before `/{id}`, which would take `events` for an id
+        .route("/events", web::get().to(event_stream::serve))
         .route("/{id}", web::get().to(get_user::serve))
         .route("", web::get().to(list_users::serve))
         .route("/{id}", web::delete().to(delete_user::serve))
         .route("/{id}", web::put().to(update_user::serve))
+        .route("/{id}", web::patch().to(patch_user::serve))
+        .route("/{id}/restore", web::post().to(restore_user::serve))
+        .route("/{id}/history", web::get().to(user_history::serve))
+        .app_data(web::JsonConfig::default().error_handler(|err, _| extractor_error(err)))
+        .app_data(web::QueryConfig::default().error_handler(|err, _| extractor_error(err)))
         .app_data(repo.clone())
+        .app_data(clock.clone())
+        .app_data(events.clone())
+        .app_data(rules.clone())
+        .app_data(cursor_key.clone())
+        .app_data(event_stream.clone())
 }
\ No newline at end of file
@@ -0,0 +1,307 @@
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, Responder, web};
+use actix_web::http::StatusCode;
+use actix_web::web::Data;
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::api::problem::Problem;
+use crate::domain::events::EventPublisher;
+use crate::api::user::etag::{etag, if_match};
+use crate::api::user::locale::date_order;
+use crate::api::user::context::change_context;
+use crate::domain::rules::UserRules;
+use crate::domain::patch_user;
+use crate::domain::patch_user::Params;
+use crate::repository::user::Repository;
+
+pub const MERGE_PATCH: &str = "application/merge-patch+json";
+pub const JSON_PATCH: &str = "application/json-patch+json";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Param {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub birthday_date: NaiveDate,
+    pub city: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PatchOperation {
+    op: String,
+    path: String,
+    value: Option<Value>,
+}
+
+/// Reads an RFC 6902 document. Only `add`, `replace`, `remove` and `test`
+/// are meaningful on a user, whose members are all strings.
+#[allow(clippy::result_large_err)]
+fn json_patch_operations(body: &[u8]) -> Result<Vec<patch_user::Operation>, Problem> {
+    let document: Vec<PatchOperation> = serde_json::from_slice(body)
+        .map_err(|err| Problem::bad_request(err.to_string()))?;
+    document.into_iter().enumerate().map(|(index, operation)| {
+        let path = operation.path;
+        let value = match (operation.op.as_str(), operation.value) {
+            ("remove", _) => String::new(),
+            ("move", _) | ("copy", _) => return Err(unprocessable(index, path, "unsupported operation")),
+            (_, Some(Value::String(value))) => value,
+            (_, None) => return Err(Problem::bad_request(format!("operation {} has no value", index))),
+            (_, Some(_)) => return Err(unprocessable(index, path, "value must be a string")),
+        };
+        match operation.op.as_str() {
+            "add" => Ok(patch_user::Operation::Add { path, value }),
+            "replace" => Ok(patch_user::Operation::Replace { path, value }),
+            "remove" => Ok(patch_user::Operation::Remove { path }),
+            "test" => Ok(patch_user::Operation::Test { path, value }),
+            op => Err(Problem::bad_request(format!("unknown operation {}", op))),
+        }
+    }).collect()
+}
+
+fn unprocessable(index: usize, path: String, reason: &'static str) -> Problem {
+    Problem::from(patch_user::Error::Unprocessable(patch_user::OperationError { index: Some(index), path, reason }))
+}
+
+/// Reads an RFC 7396 merge patch. Every user field is mandatory, so `null`
+/// (removal) and unknown members are rejected.
+fn merge_patch_request(body: &[u8]) -> Result<patch_user::Request, ()> {
+    let document: Map<String, Value> = serde_json::from_slice(body).map_err(|_| ())?;
+    let mut req = patch_user::Request::default();
+    for (field, value) in document {
+        let value = match value {
+            Value::String(value) => Some(value),
+            _ => return Err(()),
+        };
+        match field.as_str() {
+            "first_name" => req.first_name = value,
+            "last_name" => req.last_name = value,
+            "birthday_date" => req.birthday_date = value,
+            "city" => req.city = value,
+            _ => return Err(()),
+        }
+    }
+    Ok(req)
+}
+
+//#[tracing::instrument]
+pub async fn serve(repo: Data<dyn Repository>, events: Data<dyn EventPublisher>, rules: Data<UserRules>, path: web::Path<Param>, http_req: HttpRequest, body: web::Bytes) -> impl Responder {
+    let version = match if_match(&http_req) {
+        Ok(version) => version,
+        Err(response) => return response,
+    };
+    let date_order = match date_order(&http_req) {
+        Ok(date_order) => date_order,
+        Err(problem) => return problem.response(),
+    };
+    let params = Params { id: path.into_inner().id, version };
+    let context = change_context(&http_req);
+    let res = match http_req.content_type() {
+        MERGE_PATCH => match merge_patch_request(&body) {
+            Ok(req) => patch_user::execute(repo.get_ref(), events.get_ref(), rules.get_ref(), &context, params, patch_user::Request { date_order, ..req }).await,
+            Err(_) => return Problem::bad_request("the merge patch is invalid").response(),
+        },
+        JSON_PATCH => match json_patch_operations(&body) {
+            Ok(operations) => patch_user::execute_json_patch(repo.get_ref(), events.get_ref(), rules.get_ref(), &context, params, operations, date_order).await,
+            Err(problem) => return problem.response(),
+        },
+        _ => return Problem::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type")
+            .detail(format!("expected {} or {}", MERGE_PATCH, JSON_PATCH))
+            .response(),
+    };
+    match res {
+        Ok(patch_user::Response {
+               id,
+               first_name,
+               last_name,
+               birthday_date,
+               city,
+               version,
+               created_at,
+               updated_at,
+           }) => HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header(etag(version, rules.clock.today()))
+            .body(serde_json::to_string(&Response { id, first_name, last_name, birthday_date, city, created_at, updated_at }).unwrap()),
+        Err(error) => Problem::from(error).response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::events::{EventBus, EventPublisher};
+    use crate::domain::rules::UserRules;
+    use crate::repository::history::ChangeContext;
+    use crate::domain::entities::UserId;
+    use chrono::Utc;
+    use std::sync::Arc;
+    use actix_web::{App, test, web};
+    use actix_web::http::header::{CONTENT_TYPE, IF_MATCH};
+    use actix_web::http::StatusCode;
+    use actix_web::web::Data;
+    use chrono::NaiveDate;
+    use random_string::generate;
+    use uuid::Uuid;
+    use crate::api::problem::Problem;
+    use crate::api::user::patch_user::{JSON_PATCH, MERGE_PATCH, Response, serve};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::{DbUser, Repository};
+
+    async fn init(id: &Uuid) -> (Data<dyn Repository>, DbUser) {
+        let charset = "abcdefghijkl";
+        let repository: Data<dyn Repository> = Data::from(Arc::new(InMemoryRepository::new()) as Arc<dyn Repository>);
+        let db_user = repository.insert(DbUser {
+            id: *id,
+            last_name: generate(6, charset),
+            first_name: generate(6, charset),
+            city: generate(6, charset),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap();
+        (repository, db_user)
+    }
+
+    #[actix_web::test]
+    async fn test_patch_user_route_ok() {
+        let id = Uuid::new_v4();
+        let (repository, db_user) = init(&id).await;
+        let app = test::init_service(
+            App::new()
+                .route("/{id}", web::patch().to(serve))
+                .app_data(repository)
+                .app_data(Data::from(Arc::new(EventBus::new()) as Arc<dyn EventPublisher>))
+                .app_data(Data::new(UserRules::default()))).await;
+        let res = test::TestRequest::patch()
+            .uri(&format!("/{}", id))
+            .insert_header((IF_MATCH, "\"1\""))
+            .insert_header((CONTENT_TYPE, MERGE_PATCH))
+            .set_payload(r#"{"city":"paris"}"#)
+            .send_request(&app).await;
+        assert!(res.status().is_success());
+        let result: Response = test::read_body_json(res).await;
+        assert_eq!(result.city, "Paris");
+        assert_eq!(result.first_name, db_user.first_name);
+        assert_eq!(result.last_name, db_user.last_name);
+        assert_eq!(result.birthday_date, db_user.birthday_date);
+    }
+
+    #[actix_web::test]
+    async fn test_patch_user_route_fail_bad_request() {
+        let id = Uuid::new_v4();
+        let (repository, _) = init(&id).await;
+        let app = test::init_service(
+            App::new()
+                .route("/{id}", web::patch().to(serve))
+                .app_data(repository)
+                .app_data(Data::from(Arc::new(EventBus::new()) as Arc<dyn EventPublisher>))
+                .app_data(Data::new(UserRules::default()))).await;
+        for payload in [r#"{"city":null}"#, r#"{"id":"aaa"}"#, r#"{"first_name":""}"#, "[]"] {
+            let res = test::TestRequest::patch()
+                .uri(&format!("/{}", id))
+                .insert_header((IF_MATCH, "\"1\""))
+                .insert_header((CONTENT_TYPE, MERGE_PATCH))
+                .set_payload(payload)
+                .send_request(&app).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_patch_user_route_fail_unsupported_media_type() {
+        let id = Uuid::new_v4();
+        let (repository, _) = init(&id).await;
+        let app = test::init_service(
+            App::new()
+                .route("/{id}", web::patch().to(serve))
+                .app_data(repository)
+                .app_data(Data::from(Arc::new(EventBus::new()) as Arc<dyn EventPublisher>))
+                .app_data(Data::new(UserRules::default()))).await;
+        let res = test::TestRequest::patch()
+            .uri(&format!("/{}", id))
+            .insert_header((IF_MATCH, "\"1\""))
+            .insert_header((CONTENT_TYPE, "text/plain"))
+            .set_payload(r#"{"city":"paris"}"#)
+            .send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[actix_web::test]
+    async fn test_json_patch_user_route_ok() {
+        let id = Uuid::new_v4();
+        let (repository, db_user) = init(&id).await;
+        let app = test::init_service(
+            App::new()
+                .route("/{id}", web::patch().to(serve))
+                .app_data(repository)
+                .app_data(Data::from(Arc::new(EventBus::new()) as Arc<dyn EventPublisher>))
+                .app_data(Data::new(UserRules::default()))).await;
+        let payload = format!(r#"[{{"op":"test","path":"/city","value":"{}"}},{{"op":"replace","path":"/city","value":"paris"}}]"#, db_user.city);
+        let res = test::TestRequest::patch()
+            .uri(&format!("/{}", id))
+            .insert_header((IF_MATCH, "\"1\""))
+            .insert_header((CONTENT_TYPE, JSON_PATCH))
+            .set_payload(payload)
+            .send_request(&app).await;
+        assert!(res.status().is_success());
+        let result: Response = test::read_body_json(res).await;
+        assert_eq!(result.city, "Paris");
+        assert_eq!(result.first_name, db_user.first_name);
+    }
+
+    #[actix_web::test]
+    async fn test_json_patch_user_route_fail_test() {
+        let id = Uuid::new_v4();
+        let (repository, db_user) = init(&id).await;
+        let app = test::init_service(
+            App::new()
+                .route("/{id}", web::patch().to(serve))
+                .app_data(repository.clone())
+                .app_data(Data::from(Arc::new(EventBus::new()) as Arc<dyn EventPublisher>))
+                .app_data(Data::new(UserRules::default()))).await;
+        let payload = r#"[{"op":"replace","path":"/city","value":"paris"},{"op":"test","path":"/city","value":"nice"}]"#;
+        let res = test::TestRequest::patch()
+            .uri(&format!("/{}", id))
+            .insert_header((IF_MATCH, "\"1\""))
+            .insert_header((CONTENT_TYPE, JSON_PATCH))
+            .set_payload(payload)
+            .send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let result: Problem = test::read_body_json(res).await;
+        assert_eq!(result.code, "test_failed");
+        assert_eq!(result.extensions["index"], 1);
+        assert_eq!(result.extensions["path"], "/city");
+        assert_eq!(repository.get(UserId::from(id)).await.unwrap().city, db_user.city);
+    }
+
+    #[actix_web::test]
+    async fn test_json_patch_user_route_fail_unprocessable() {
+        let id = Uuid::new_v4();
+        let (repository, _) = init(&id).await;
+        let app = test::init_service(
+            App::new()
+                .route("/{id}", web::patch().to(serve))
+                .app_data(repository)
+                .app_data(Data::from(Arc::new(EventBus::new()) as Arc<dyn EventPublisher>))
+                .app_data(Data::new(UserRules::default()))).await;
+        for payload in [r#"[{"op":"remove","path":"/city"}]"#, r#"[{"op":"move","from":"/city","path":"/last_name"}]"#, r#"[{"op":"add","path":"/city","value":3}]"#] {
+            let res = test::TestRequest::patch()
+                .uri(&format!("/{}", id))
+                .insert_header((IF_MATCH, "\"1\""))
+                .insert_header((CONTENT_TYPE, JSON_PATCH))
+                .set_payload(payload)
+                .send_request(&app).await;
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use actix_web::web::Data;
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+use crate::api::user::etag::etag;
+use crate::api::user::context::change_context;
+use crate::api::problem::Problem;
+use crate::domain::clock::Clock;
+use crate::domain::events::EventPublisher;
+use crate::domain::restore_user;
+use crate::repository::user::Repository;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Param {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub birthday_date: NaiveDate,
+    pub city: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+//#[tracing::instrument]
+pub async fn serve(repo: Data<dyn Repository>, events: Data<dyn EventPublisher>, clock: Data<dyn Clock>, path: web::Path<Param>, http_req: HttpRequest) -> impl Responder {
+    let req = restore_user::Request { id: path.into_inner().id };
+    match restore_user::execute(repo.get_ref(), events.get_ref(), &change_context(&http_req), req).await {
+        Ok(restore_user::Response {
+               id,
+               first_name,
+               last_name,
+               birthday_date,
+               city,
+               version,
+               created_at,
+               updated_at,
+           }) => HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header(etag(version, clock.today()))
+            .body(serde_json::to_string(&Response { id, first_name, last_name, birthday_date, city, created_at, updated_at }).unwrap()),
+        Err(error) => Problem::from(error).response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::clock::{Clock, FixedClock};
+    use crate::domain::events::{EventBus, EventPublisher};
+    use crate::repository::history::ChangeContext;
+    use crate::domain::entities::UserId;
+    use std::sync::Arc;
+    use actix_web::{App, test, web};
+    use actix_web::http::{header, StatusCode};
+    use actix_web::web::Data;
+    use chrono::{NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::api::problem::Problem;
+    use crate::api::user::restore_user::{serve, Response};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::{DbUser, Repository};
+
+    #[actix_web::test]
+    async fn test_restore_user_route_ok() {
+        let repository = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        repository.insert(DbUser {
+            id,
+            first_name: "Hugo".to_string(),
+            last_name: "Muf".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            city: "Nice".to_string(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap();
+        let repo: Data<dyn Repository> = Data::from(Arc::new(repository) as Arc<dyn Repository>);
+        let app = test::init_service(
+            App::new()
+                .route("/{id}/restore", web::post().to(serve))
+                .app_data(repo.clone())
+                .app_data(Data::from(Arc::new(EventBus::new()) as Arc<dyn EventPublisher>))
+                .app_data(Data::from(Arc::new(FixedClock(NaiveDate::from_ymd_opt(2022, 10, 3).unwrap())) as Arc<dyn Clock>))).await;
+        let res = test::TestRequest::post().uri(&format!("/{}/restore", id)).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let problem: Problem = test::read_body_json(res).await;
+        assert_eq!(problem.code, "user_not_deleted");
+
+        repo.delete(UserId::from(id), 1, &ChangeContext::test()).await.unwrap();
+        let res = test::TestRequest::post().uri(&format!("/{}/restore", id)).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::ETAG).unwrap(), "\"3-2022-10-03\"");
+        let result: Response = test::read_body_json(res).await;
+        assert_eq!(result.id, id);
+        assert!(repo.get(UserId::from(id)).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_restore_user_route_not_found() {
+        let repository = InMemoryRepository::new();
+        let repo: Data<dyn Repository> = Data::from(Arc::new(repository) as Arc<dyn Repository>);
+        let app = test::init_service(
+            App::new()
+                .route("/{id}/restore", web::post().to(serve))
+                .app_data(repo)
+                .app_data(Data::from(Arc::new(EventBus::new()) as Arc<dyn EventPublisher>))
+                .app_data(Data::from(Arc::new(FixedClock(NaiveDate::from_ymd_opt(2022, 10, 3).unwrap())) as Arc<dyn Clock>))).await;
+        let res = test::TestRequest::post().uri(&format!("/{}/restore", Uuid::new_v4())).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND)
+    }
+}
@@ -0,0 +1,153 @@
+use actix_web::{HttpResponse, Responder, web};
+use actix_web::web::Data;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use serde::Serialize;
+use crate::api::user::etag::cache_control;
+use crate::api::problem::Problem;
+use crate::domain::user_history;
+use crate::repository::history::Operation;
+use crate::repository::user::{DbUser, Repository};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Param {
+    pub id: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Params {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// The user fields as they were before or after a change.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub first_name: String,
+    pub last_name: String,
+    pub birthday_date: NaiveDate,
+    pub city: String,
+    pub version: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl From<DbUser> for Snapshot {
+    fn from(user: DbUser) -> Self {
+        Snapshot {
+            first_name: user.first_name,
+            last_name: user.last_name,
+            birthday_date: user.birthday_date,
+            city: user.city,
+            version: user.version,
+            deleted_at: user.deleted_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub seq: i64,
+    pub operation: Operation,
+    pub old: Option<Snapshot>,
+    pub new: Option<Snapshot>,
+    pub actor: String,
+    pub request_id: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageResponse {
+    pub items: Vec<Response>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub next_offset: Option<i64>,
+}
+//#[tracing::instrument]
+pub async fn serve(repo: Data<dyn Repository>, path: web::Path<Param>, params: web::Query<Params>) -> impl Responder {
+    let params = params.into_inner();
+    let req = user_history::Request { id: path.into_inner().id, limit: params.limit, offset: params.offset };
+    match user_history::execute(repo.get_ref(), req).await {
+        Ok(page) => {
+            let items = page.changes.into_iter().map(|change| Response {
+                seq: change.seq,
+                operation: change.operation,
+                old: change.old.map(Snapshot::from),
+                new: change.new.map(Snapshot::from),
+                actor: change.actor,
+                request_id: change.request_id,
+                changed_at: change.changed_at,
+            }).collect();
+            HttpResponse::Ok()
+                .content_type("application/json")
+                .insert_header(cache_control())
+                .body(serde_json::to_string(&PageResponse {
+                    items,
+                    total: page.total,
+                    limit: page.limit,
+                    offset: page.offset,
+                    next_offset: page.next_offset,
+                }).unwrap())
+        }
+        Err(error) => Problem::from(error).response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::{App, test, web};
+    use actix_web::http::StatusCode;
+    use actix_web::web::Data;
+    use chrono::{NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::api::user::user_history::{serve, PageResponse};
+    use crate::domain::entities::UserId;
+    use crate::repository::history::{ChangeContext, Operation};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::{DbUser, Repository};
+
+    #[actix_web::test]
+    async fn test_user_history_route_ok() {
+        let repository = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        let db_user = repository.insert(DbUser {
+            id,
+            first_name: "Hugo".to_string(),
+            last_name: "Muf".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            city: "Nice".to_string(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap();
+        repository.update(UserId::from(id), DbUser { city: "Paris".to_string(), ..db_user }, 1, &ChangeContext::test()).await.unwrap();
+        let repo: Data<dyn Repository> = Data::from(Arc::new(repository) as Arc<dyn Repository>);
+        let app = test::init_service(
+            App::new()
+                .route("/{id}/history", web::get().to(serve))
+                .app_data(repo)).await;
+        let res = test::TestRequest::get().uri(&format!("/{}/history?limit=1&offset=1", id)).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let page: PageResponse = test::read_body_json(res).await;
+        assert_eq!(page.total, 2);
+        assert_eq!(page.items[0].operation, Operation::Update);
+        assert_eq!(page.items[0].old.as_ref().unwrap().city, "Nice");
+        assert_eq!(page.items[0].new.as_ref().unwrap().city, "Paris");
+        assert_eq!(page.items[0].actor, "test");
+    }
+
+    #[actix_web::test]
+    async fn test_user_history_route_not_found() {
+        let repository = InMemoryRepository::new();
+        let repo: Data<dyn Repository> = Data::from(Arc::new(repository) as Arc<dyn Repository>);
+        let app = test::init_service(
+            App::new()
+                .route("/{id}/history", web::get().to(serve))
+                .app_data(repo)).await;
+        let res = test::TestRequest::get().uri(&format!("/{}/history", Uuid::new_v4())).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
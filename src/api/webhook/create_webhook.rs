@@ -0,0 +1,84 @@
+use actix_web::{HttpResponse, Responder, web};
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use serde::Deserialize;
+use serde::Serialize;
+use crate::api::problem::Problem;
+use crate::domain::create_webhook;
+use crate::domain::entities::WebhookRules;
+use crate::repository::webhook::WebhookRepository;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Request {
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub secret: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+//#[tracing::instrument]
+pub async fn serve(repo: Data<dyn WebhookRepository>, rules: Data<WebhookRules>, req: web::Json<Request>) -> impl Responder {
+    let req = create_webhook::Request { url: req.0.url, events: req.0.events };
+    match create_webhook::execute(repo.get_ref(), rules.get_ref(), req).await {
+        Ok(create_webhook::Response { id, url, events, secret, active, created_at }) => HttpResponse::Created()
+            .content_type("application/json")
+            .body(serde_json::to_string(&Response { id, url, events, secret, active, created_at }).unwrap()),
+        Err(error) => Problem::from(error).response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::{App, test, web};
+    use actix_web::http::StatusCode;
+    use actix_web::web::Data;
+    use serde_json::json;
+    use crate::api::problem::Problem;
+    use crate::api::webhook::create_webhook::{serve, Response};
+    use crate::domain::entities::WebhookRules;
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::webhook::WebhookRepository;
+
+    #[actix_web::test]
+    async fn test_create_webhook_route() {
+        let repo: Data<dyn WebhookRepository> = Data::from(Arc::new(InMemoryRepository::new()) as Arc<dyn WebhookRepository>);
+        let app = test::init_service(App::new()
+            .route("/", web::post().to(serve))
+            .app_data(repo.clone())
+            .app_data(Data::new(WebhookRules::default()))).await;
+        let res = test::TestRequest::post().uri("/")
+            .set_json(json!({"url": "https://partner.example/hooks", "events": ["UserCreated"]}))
+            .send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let created: Response = test::read_body_json(res).await;
+        assert!(created.secret.starts_with("whsec_"));
+        assert_eq!(created.events, vec!["UserCreated"]);
+        assert_eq!(repo.get_webhook(created.id).await.unwrap().secret, created.secret);
+
+        let res = test::TestRequest::post().uri("/")
+            .set_json(json!({"url": "ftp://partner.example", "events": ["UserBorn"]}))
+            .send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let problem: Problem = test::read_body_json(res).await;
+        assert_eq!(problem.extensions["errors"], json!([
+            {"field": "url", "code": "invalid_url"},
+            {"field": "events", "code": "unknown_event"},
+        ]));
+
+        let res = test::TestRequest::post().uri("/")
+            .set_json(json!({"url": "https://169.254.169.254/latest/meta-data"}))
+            .send_request(&app).await;
+        let problem: Problem = test::read_body_json(res).await;
+        assert_eq!(problem.extensions["errors"], json!([{"field": "url", "code": "private_address"}]));
+    }
+}
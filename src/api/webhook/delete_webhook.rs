@@ -0,0 +1,60 @@
+use actix_web::{HttpResponse, Responder, web};
+use actix_web::web::Data;
+use serde::Deserialize;
+use serde::Serialize;
+use crate::api::problem::Problem;
+use crate::domain::delete_webhook;
+use crate::repository::webhook::WebhookRepository;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Param {
+    pub id: String,
+}
+//#[tracing::instrument]
+pub async fn serve(repo: Data<dyn WebhookRepository>, path: web::Path<Param>) -> impl Responder {
+    let req = delete_webhook::Request { id: path.into_inner().id };
+    match delete_webhook::execute(repo.get_ref(), req).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(error) => Problem::from(error).response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::{App, test, web};
+    use actix_web::http::StatusCode;
+    use actix_web::web::Data;
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::api::problem::Problem;
+    use crate::api::webhook::delete_webhook::serve;
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::webhook::{DbWebhook, WebhookError, WebhookRepository};
+
+    #[actix_web::test]
+    async fn test_delete_webhook_route() {
+        let repository = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        repository.insert_webhook(DbWebhook {
+            id,
+            url: "https://partner.example/hooks".to_string(),
+            events: vec![],
+            secret: "whsec_test".to_string(),
+            active: true,
+            consecutive_failures: 0,
+            disabled_at: None,
+            created_at: Utc::now(),
+        }).await.unwrap();
+        let repo: Data<dyn WebhookRepository> = Data::from(Arc::new(repository) as Arc<dyn WebhookRepository>);
+        let app = test::init_service(App::new().route("/{id}", web::delete().to(serve)).app_data(repo.clone())).await;
+        let res = test::TestRequest::delete().uri(&format!("/{}", id)).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(repo.get_webhook(id).await.err().unwrap(), WebhookError::NotFound);
+
+        let res = test::TestRequest::delete().uri(&format!("/{}", id)).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let problem: Problem = test::read_body_json(res).await;
+        assert_eq!(problem.code, "webhook_not_found");
+    }
+}
@@ -0,0 +1,121 @@
+use actix_web::{HttpResponse, Responder, web};
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde::Serialize;
+use crate::api::problem::Problem;
+use crate::domain::list_webhook_deliveries;
+use crate::repository::webhook::{DbDelivery, DeliveryStatus, WebhookRepository};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Param {
+    pub id: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Params {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub message_id: i64,
+    pub event_type: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<DbDelivery> for Response {
+    fn from(delivery: DbDelivery) -> Self {
+        Response {
+            message_id: delivery.message_id,
+            event_type: delivery.event_type,
+            status: delivery.status,
+            attempts: delivery.attempts,
+            response_status: delivery.response_status,
+            last_error: delivery.last_error,
+            next_attempt_at: delivery.next_attempt_at,
+            delivered_at: delivery.delivered_at,
+            created_at: delivery.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageResponse {
+    pub items: Vec<Response>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub next_offset: Option<i64>,
+}
+//#[tracing::instrument]
+pub async fn serve(repo: Data<dyn WebhookRepository>, path: web::Path<Param>, params: web::Query<Params>) -> impl Responder {
+    let params = params.into_inner();
+    let req = list_webhook_deliveries::Request { id: path.into_inner().id, limit: params.limit, offset: params.offset };
+    match list_webhook_deliveries::execute(repo.get_ref(), req).await {
+        Ok(page) => HttpResponse::Ok()
+            .content_type("application/json")
+            .body(serde_json::to_string(&PageResponse {
+                items: page.deliveries.into_iter().map(Response::from).collect(),
+                total: page.total,
+                limit: page.limit,
+                offset: page.offset,
+                next_offset: page.next_offset,
+            }).unwrap()),
+        Err(error) => Problem::from(error).response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::{App, test, web};
+    use actix_web::http::StatusCode;
+    use actix_web::web::Data;
+    use chrono::Utc;
+    use serde_json::json;
+    use uuid::Uuid;
+    use crate::api::webhook::list_webhook_deliveries::{serve, PageResponse};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::webhook::{DbWebhook, DeliveryStatus, WebhookRepository};
+
+    #[actix_web::test]
+    async fn test_list_webhook_deliveries_route() {
+        let repository = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        repository.insert_webhook(DbWebhook {
+            id,
+            url: "https://partner.example/hooks".to_string(),
+            events: vec![],
+            secret: "whsec_test".to_string(),
+            active: true,
+            consecutive_failures: 0,
+            disabled_at: None,
+            created_at: Utc::now(),
+        }).await.unwrap();
+        for message_id in 1..=3 {
+            repository.queue_deliveries(message_id, "UserCreated", &json!({"id": message_id})).await.unwrap();
+        }
+        let repo: Data<dyn WebhookRepository> = Data::from(Arc::new(repository) as Arc<dyn WebhookRepository>);
+        let app = test::init_service(App::new().route("/{id}/deliveries", web::get().to(serve)).app_data(repo)).await;
+        let res = test::TestRequest::get().uri(&format!("/{}/deliveries?limit=2", id)).send_request(&app).await;
+        assert!(res.status().is_success());
+        let page: PageResponse = test::read_body_json(res).await;
+        assert_eq!(page.total, 3);
+        assert_eq!(page.next_offset, Some(2));
+        assert_eq!(page.items.iter().map(|item| item.message_id).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!(page.items[0].status, DeliveryStatus::Pending);
+
+        let res = test::TestRequest::get().uri(&format!("/{}/deliveries", Uuid::new_v4())).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = test::TestRequest::get().uri(&format!("/{}/deliveries?limit=0", id)).send_request(&app).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
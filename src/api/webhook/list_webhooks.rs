@@ -0,0 +1,71 @@
+use actix_web::{HttpResponse, Responder};
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use serde::Deserialize;
+use serde::Serialize;
+use crate::api::problem::Problem;
+use crate::domain::list_webhooks;
+use crate::repository::webhook::WebhookRepository;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub consecutive_failures: i32,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+//#[tracing::instrument]
+pub async fn serve(repo: Data<dyn WebhookRepository>) -> impl Responder {
+    match list_webhooks::execute(repo.get_ref()).await {
+        Ok(webhooks) => {
+            let webhooks: Vec<Response> = webhooks.into_iter()
+                .map(|list_webhooks::Response { id, url, events, active, consecutive_failures, disabled_at, created_at }| Response {
+                    id, url, events, active, consecutive_failures, disabled_at, created_at,
+                })
+                .collect();
+            HttpResponse::Ok()
+                .content_type("application/json")
+                .body(serde_json::to_string(&webhooks).unwrap())
+        }
+        Err(error) => Problem::from(error).response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::{App, test, web};
+    use actix_web::web::Data;
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::api::webhook::list_webhooks::serve;
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::webhook::{DbWebhook, WebhookRepository};
+
+    #[actix_web::test]
+    async fn test_list_webhooks_route_hides_secrets() {
+        let repository = InMemoryRepository::new();
+        repository.insert_webhook(DbWebhook {
+            id: Uuid::new_v4(),
+            url: "https://partner.example/hooks".to_string(),
+            events: vec![],
+            secret: "whsec_test".to_string(),
+            active: true,
+            consecutive_failures: 0,
+            disabled_at: None,
+            created_at: Utc::now(),
+        }).await.unwrap();
+        let repo: Data<dyn WebhookRepository> = Data::from(Arc::new(repository) as Arc<dyn WebhookRepository>);
+        let app = test::init_service(App::new().route("/", web::get().to(serve)).app_data(repo)).await;
+        let res = test::TestRequest::get().uri("/").send_request(&app).await;
+        assert!(res.status().is_success());
+        let webhooks: Vec<serde_json::Value> = test::read_body_json(res).await;
+        assert_eq!(webhooks.len(), 1);
+        assert_eq!(webhooks[0]["url"], "https://partner.example/hooks");
+        assert!(webhooks[0].get("secret").is_none());
+    }
+}
@@ -0,0 +1,22 @@
+use actix_web::{Scope, web};
+use actix_web::web::Data;
+use crate::api::problem::extractor_error;
+use crate::domain::entities::WebhookRules;
+use crate::repository::webhook::WebhookRepository;
+
+mod create_webhook;
+mod list_webhooks;
+mod delete_webhook;
+mod list_webhook_deliveries;
+
+pub fn webhook_service(repo: &Data<dyn WebhookRepository>, rules: &Data<WebhookRules>) -> Scope {
+    web::scope("/webhooks")
+        .route("", web::post().to(create_webhook::serve))
+        .route("", web::get().to(list_webhooks::serve))
+        .route("/{id}", web::delete().to(delete_webhook::serve))
+        .route("/{id}/deliveries", web::get().to(list_webhook_deliveries::serve))
+        .app_data(web::JsonConfig::default().error_handler(|err, _| extractor_error(err)))
+        .app_data(web::QueryConfig::default().error_handler(|err, _| extractor_error(err)))
+        .app_data(repo.clone())
+        .app_data(rules.clone())
+}
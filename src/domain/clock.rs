@@ -0,0 +1,24 @@
+use chrono::{NaiveDate, Utc};
+
+/// Source of the current date, injected so birthday rules and ages do not depend on when tests run.
+pub trait Clock: Send + Sync {
+    fn today(&self) -> NaiveDate;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn today(&self) -> NaiveDate {
+        Utc::now().date_naive()
+    }
+}
+
+#[cfg(test)]
+pub struct FixedClock(pub NaiveDate);
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn today(&self) -> NaiveDate {
+        self.0
+    }
+}
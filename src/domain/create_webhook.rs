@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::domain::entities::{EventFilter, ValidationErrors, WebhookRules, WebhookUrl};
+use crate::repository::webhook::{DbWebhook, WebhookError, WebhookRepository};
+
+#[derive(Debug)]
+pub struct Request {
+    pub url: String,
+    /// Event types to receive, every type when empty.
+    pub events: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    /// Key of the `X-Webhook-Signature` HMAC, only ever returned here.
+    pub secret: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    BadRequest(ValidationErrors),
+    Unknown,
+}
+
+/// 244 random bits from two v4 uuids.
+fn secret() -> String {
+    format!("whsec_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+pub async fn execute(repo: &dyn WebhookRepository, rules: &WebhookRules, req: Request) -> Result<Response, Error> {
+    let mut errors = ValidationErrors::new();
+    let (url, events) = match (
+        errors.check("url", WebhookUrl::parse(&req.url, rules)),
+        errors.check("events", EventFilter::try_from(req.events)),
+    ) {
+        (Some(url), Some(events)) => (url, events),
+        _ => return Err(Error::BadRequest(errors)),
+    };
+    let res = repo.insert_webhook(DbWebhook {
+        id: Uuid::new_v4(),
+        url: String::from(url),
+        events: Vec::from(events),
+        secret: secret(),
+        active: true,
+        consecutive_failures: 0,
+        disabled_at: None,
+        // set by the repository
+        created_at: Utc::now(),
+    }).await;
+    match res {
+        Ok(DbWebhook { id, url, events, secret, active, created_at, .. }) => Ok(Response { id, url, events, secret, active, created_at }),
+        Err(WebhookError::NotFound) | Err(WebhookError::Unknown) => Err(Error::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::create_webhook::{execute, Error, Request};
+    use crate::domain::entities::{FieldError, ValidationErrors, WebhookRules};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::webhook::WebhookRepository;
+
+    #[tokio::test]
+    async fn create_webhook_domain_works() {
+        let repo = InMemoryRepository::new();
+        let res = execute(&repo, &WebhookRules::default(), Request {
+            url: String::from("https://partner.example/hooks"),
+            events: vec![String::from("UserCreated")],
+        }).await.unwrap();
+        assert!(res.active);
+        assert!(res.secret.starts_with("whsec_"));
+        let stored = repo.get_webhook(res.id).await.unwrap();
+        assert_eq!(stored.url, "https://partner.example/hooks");
+        assert_eq!(stored.events, vec![String::from("UserCreated")]);
+        assert_eq!(stored.secret, res.secret);
+    }
+
+    #[tokio::test]
+    async fn create_webhook_domain_fail_bad_request() {
+        let repo = InMemoryRepository::new();
+        let res = execute(&repo, &WebhookRules::default(), Request {
+            url: String::from("partner.example"),
+            events: vec![String::from("UserPurged")],
+        }).await;
+        assert_eq!(res.err().unwrap(), Error::BadRequest(ValidationErrors(vec![
+            FieldError { field: "url", code: "invalid_url" },
+            FieldError { field: "events", code: "unknown_event" },
+        ])));
+    }
+}
@@ -0,0 +1,60 @@
+use uuid::Uuid;
+use crate::domain::entities::{ValidationErrors, WebhookId};
+use crate::repository::webhook::{WebhookError, WebhookRepository};
+
+#[derive(Debug)]
+pub struct Request {
+    pub id: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    BadRequest(ValidationErrors),
+    NotFound,
+    Unknown,
+}
+
+pub async fn execute(repo: &dyn WebhookRepository, req: Request) -> Result<(), Error> {
+    let id = WebhookId::try_from(req.id).map_err(|error| Error::BadRequest(ValidationErrors::single("id", error)))?;
+    match repo.delete_webhook(Uuid::from(id)).await {
+        Ok(()) => Ok(()),
+        Err(WebhookError::NotFound) => Err(Error::NotFound),
+        Err(WebhookError::Unknown) => Err(Error::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::domain::delete_webhook::{execute, Error, Request};
+    use crate::domain::entities::{FieldError, ValidationErrors};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::webhook::{DbWebhook, WebhookError, WebhookRepository};
+
+    #[tokio::test]
+    async fn delete_webhook_domain_works() {
+        let repo = InMemoryRepository::new();
+        let webhook = repo.insert_webhook(DbWebhook {
+            id: Uuid::new_v4(),
+            url: String::from("https://partner.example/hooks"),
+            events: vec![],
+            secret: String::from("whsec_test"),
+            active: true,
+            consecutive_failures: 0,
+            disabled_at: None,
+            created_at: Utc::now(),
+        }).await.unwrap();
+        execute(&repo, Request { id: webhook.id.to_string() }).await.unwrap();
+        assert_eq!(repo.get_webhook(webhook.id).await.err().unwrap(), WebhookError::NotFound);
+        let res = execute(&repo, Request { id: webhook.id.to_string() }).await;
+        assert_eq!(res.err().unwrap(), Error::NotFound);
+    }
+
+    #[tokio::test]
+    async fn delete_webhook_domain_fail_bad_request() {
+        let repo = InMemoryRepository::new();
+        let res = execute(&repo, Request { id: String::from("nope") }).await;
+        assert_eq!(res.err().unwrap(), Error::BadRequest(ValidationErrors(vec![FieldError { field: "id", code: "malformed" }])));
+    }
+}
@@ -1,8 +1,280 @@
 use std::cmp::PartialEq;
 use std::convert::TryFrom;
-use std::fmt::Display;
-use chrono::{NaiveDate, ParseResult};
-use uuid::{Error, Uuid};
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::str::FromStr;
+use chrono::{DateTime, Datelike, NaiveDate};
+use unicode_normalization::UnicodeNormalization;
+use url::{Host, Url};
+use uuid::Uuid;
+use crate::domain::clock::Clock;
+use crate::domain::events::UserEvent;
+
+pub const DEFAULT_MAX_LENGTH: usize = 100;
+
+
+/// Limits on names and cities, in characters once normalised. Built from the configuration
+/// in `main` and handed to the use cases that validate names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRules {
+    pub name_max_length: usize,
+    pub city_max_length: usize,
+}
+
+impl Default for TextRules {
+    fn default() -> Self {
+        TextRules { name_max_length: DEFAULT_MAX_LENGTH, city_max_length: DEFAULT_MAX_LENGTH }
+    }
+}
+
+/// Implemented by every entity error, the code is what clients receive.
+pub trait ValidationCode {
+    fn code(&self) -> &'static str;
+}
+
+/// A field refused by its entity, e.g. `birthday_date` / `invalid_format`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub code: &'static str,
+}
+
+/// Collects the failures of every field of a request instead of stopping at the first one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationErrors(pub Vec<FieldError>);
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        ValidationErrors(Vec::new())
+    }
+
+    pub fn single<E: ValidationCode>(field: &'static str, error: E) -> Self {
+        ValidationErrors(vec![FieldError { field, code: error.code() }])
+    }
+
+    /// Returns the entity, or records its error under `field` and returns `None`.
+    pub fn check<T, E: ValidationCode>(&mut self, field: &'static str, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.0.push(FieldError { field, code: error.code() });
+                None
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextError {
+    Empty,
+    TooLong,
+    InvalidCharacters,
+}
+
+impl ValidationCode for TextError {
+    fn code(&self) -> &'static str {
+        match self {
+            TextError::Empty => "empty",
+            TextError::TooLong => "too_long",
+            TextError::InvalidCharacters => "invalid_characters",
+        }
+    }
+}
+
+/// NFC normalisation, trimming and collapsing of inner whitespace to a single space,
+/// so `"  paris "` and `"paris"` end up identical.
+fn normalize_text(n: &str, max_length: usize) -> Result<String, TextError> {
+    let n: String = n.trim().nfc().collect();
+    if n.chars().any(char::is_control) {
+        return Err(TextError::InvalidCharacters);
+    }
+    let n = n.split_whitespace().collect::<Vec<_>>().join(" ");
+    if n.is_empty() {
+        Err(TextError::Empty)
+    } else if n.chars().count() > max_length {
+        Err(TextError::TooLong)
+    } else {
+        Ok(n)
+    }
+}
+
+fn normalize_name(n: &str, rules: &TextRules) -> Result<String, TextError> {
+    let n = normalize_text(n, rules.name_max_length)?;
+    if n.chars().any(char::is_numeric) {
+        return Err(TextError::InvalidCharacters);
+    }
+    Ok(n)
+}
+
+/// Lower case with an upper case letter at the start of every word, `"SAINT-éTIENNE"` becomes
+/// `"Saint-Étienne"`. Matches Postgres `initcap` so stored rows can be migrated the same way.
+fn canonical_case(n: &str) -> String {
+    let mut result = String::with_capacity(n.len());
+    let mut word_start = true;
+    for c in n.chars() {
+        if word_start {
+            result.extend(c.to_uppercase());
+        } else {
+            result.extend(c.to_lowercase());
+        }
+        word_start = !c.is_alphanumeric();
+    }
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BirthdayDateError {
+    InvalidFormat,
+    InFuture,
+    TooOld,
+    TooYoung,
+}
+
+impl ValidationCode for BirthdayDateError {
+    fn code(&self) -> &'static str {
+        match self {
+            BirthdayDateError::InvalidFormat => "invalid_format",
+            BirthdayDateError::InFuture => "in_future",
+            BirthdayDateError::TooOld => "too_old",
+            BirthdayDateError::TooYoung => "too_young",
+        }
+    }
+}
+
+/// Order of day and month in numeric dates such as `03/10/1994`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateOrder {
+    DayMonth,
+    MonthDay,
+}
+
+impl FromStr for DateOrder {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dmy" => Ok(DateOrder::DayMonth),
+            "mdy" => Ok(DateOrder::MonthDay),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A birthday input format, stored dates and responses are always ISO 8601.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateFormat {
+    /// `1994-10-03`
+    Iso,
+    /// `1994-10-03T00:00:00Z`, the time and offset are dropped.
+    Rfc3339,
+    /// `03/10/1994` or `03.10.1994`, read according to the `DateOrder`.
+    Numeric,
+    /// Any chrono pattern, e.g. `%d %b %Y` for `3 Oct 1994`.
+    Pattern(String),
+}
+
+impl FromStr for DateFormat {
+    type Err = ();
+
+    /// A pattern must be able to read back the date it writes, so it needs a valid
+    /// specifier for each of the day, the month and the year.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" => Err(()),
+            "iso" => Ok(DateFormat::Iso),
+            "rfc3339" => Ok(DateFormat::Rfc3339),
+            "numeric" => Ok(DateFormat::Numeric),
+            pattern => {
+                let sample = NaiveDate::from_ymd_opt(1994, 10, 3).unwrap();
+                let mut written = String::new();
+                write!(written, "{}", sample.format(pattern)).map_err(|_| ())?;
+                match NaiveDate::parse_from_str(&written, pattern) {
+                    Ok(date) if date == sample => Ok(DateFormat::Pattern(pattern.to_string())),
+                    _ => Err(()),
+                }
+            }
+        }
+    }
+}
+
+impl DateFormat {
+    fn parse(&self, n: &str, order: DateOrder) -> Option<NaiveDate> {
+        match self {
+            DateFormat::Iso => NaiveDate::parse_from_str(n, "%Y-%m-%d").ok(),
+            DateFormat::Rfc3339 => DateTime::parse_from_rfc3339(n).ok().map(|date| date.naive_local().date()),
+            DateFormat::Numeric => {
+                let patterns = match order {
+                    DateOrder::DayMonth => ["%d/%m/%Y", "%d.%m.%Y"],
+                    DateOrder::MonthDay => ["%m/%d/%Y", "%m.%d.%Y"],
+                };
+                patterns.iter().find_map(|pattern| NaiveDate::parse_from_str(n, pattern).ok())
+            }
+            DateFormat::Pattern(pattern) => NaiveDate::parse_from_str(n, pattern).ok(),
+        }
+    }
+}
+
+/// Plausibility limits on birthdays: nobody born before `min_year` nor younger than `min_age`.
+/// `formats` are tried in order, `date_order` applies when the request does not choose one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BirthdayRules {
+    pub min_year: i32,
+    pub min_age: u32,
+    pub formats: Vec<DateFormat>,
+    pub date_order: DateOrder,
+}
+
+impl Default for BirthdayRules {
+    fn default() -> Self {
+        BirthdayRules {
+            min_year: 1900,
+            min_age: 0,
+            formats: vec![
+                DateFormat::Iso,
+                DateFormat::Rfc3339,
+                DateFormat::Numeric,
+                DateFormat::Pattern(String::from("%d %b %Y")),
+            ],
+            date_order: DateOrder::DayMonth,
+        }
+    }
+}
+
+/// The birthday in `year`, people born on February 29th celebrate on the 28th in common years.
+fn anniversary(birthday: NaiveDate, year: i32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, birthday.month(), birthday.day())
+        .unwrap_or_else(|| NaiveDate::from_ymd_opt(year, 2, 28).unwrap())
+}
+
+/// Completed years between `birthday` and `today`.
+pub fn age(birthday: NaiveDate, today: NaiveDate) -> u32 {
+    let years = today.year() - birthday.year();
+    let years = if anniversary(birthday, today.year()) > today { years - 1 } else { years };
+    years.max(0) as u32
+}
+
+/// Next anniversary of `birthday`, `today` itself when it is the day.
+pub fn next_birthday(birthday: NaiveDate, today: NaiveDate) -> NaiveDate {
+    let this_year = anniversary(birthday, today.year());
+    if this_year >= today { this_year } else { anniversary(birthday, today.year() + 1) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserIdError {
+    Malformed,
+}
+
+impl ValidationCode for UserIdError {
+    fn code(&self) -> &'static str {
+        match self {
+            UserIdError::Malformed => "malformed",
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct BirthdayDate(NaiveDate);
@@ -13,14 +285,18 @@ impl From<BirthdayDate> for NaiveDate {
     }
 }
 
-impl TryFrom<String> for BirthdayDate {
-    type Error = ();
-
-    fn try_from(n: String) -> Result<Self, Self::Error> {
-        let parse_from_str = NaiveDate::parse_from_str;
-        match parse_from_str(&n, "%Y-%m-%d") {
-            Ok(date) => { Ok(Self(date)) }
-            Err(_) => { Err(()) }
+impl BirthdayDate {
+    /// `order` overrides the `date_order` of the rules, the clock tells what today is.
+    pub fn parse(n: &str, rules: &BirthdayRules, clock: &dyn Clock, order: Option<DateOrder>) -> Result<Self, BirthdayDateError> {
+        let order = order.unwrap_or(rules.date_order);
+        let today = clock.today();
+        let n = n.trim();
+        match rules.formats.iter().find_map(|format| format.parse(n, order)) {
+            Some(date) if date > today => { Err(BirthdayDateError::InFuture) }
+            Some(date) if date.year() < rules.min_year => { Err(BirthdayDateError::TooOld) }
+            Some(date) if age(date, today) < rules.min_age => { Err(BirthdayDateError::TooYoung) }
+            Some(date) => { Ok(Self(date)) }
+            None => { Err(BirthdayDateError::InvalidFormat) }
         }
     }
 }
@@ -48,15 +324,9 @@ impl BirthdayDate {
 #[derive(Clone)]
 pub struct FirstName(String);
 
-impl TryFrom<String> for FirstName {
-    type Error = ();
-
-    fn try_from(n: String) -> Result<Self, Self::Error> {
-        if n.is_empty() {
-            Err(())
-        } else {
-            Ok(Self(n))
-        }
+impl FirstName {
+    pub fn parse(n: &str, rules: &TextRules) -> Result<Self, TextError> {
+        Ok(Self(normalize_name(n, rules)?))
     }
 }
 
@@ -80,15 +350,9 @@ impl FirstName {
 #[derive(Clone)]
 pub struct LastName(String);
 
-impl TryFrom<String> for LastName {
-    type Error = ();
-
-    fn try_from(n: String) -> Result<Self, Self::Error> {
-        if n.is_empty() {
-            Err(())
-        } else {
-            Ok(Self(n))
-        }
+impl LastName {
+    pub fn parse(n: &str, rules: &TextRules) -> Result<Self, TextError> {
+        Ok(Self(normalize_name(n, rules)?))
     }
 }
 
@@ -114,15 +378,9 @@ impl LastName {
 #[derive(Clone)]
 pub struct CityName(String);
 
-impl TryFrom<String> for CityName {
-    type Error = ();
-
-    fn try_from(n: String) -> Result<Self, Self::Error> {
-        if n.is_empty() {
-            Err(())
-        } else {
-            Ok(Self(n))
-        }
+impl CityName {
+    pub fn parse(n: &str, rules: &TextRules) -> Result<Self, TextError> {
+        Ok(Self(canonical_case(&normalize_text(n, rules.city_max_length)?)))
     }
 }
 
@@ -135,7 +393,7 @@ impl From<CityName> for String {
 #[cfg(test)]
 impl CityName {
     pub fn name() -> Self {
-        Self(String::from("nice"))
+        Self(String::from("Nice"))
     }
 
     pub fn bad() -> Self {
@@ -144,7 +402,7 @@ impl CityName {
 }
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct UserId(Uuid);
 
 impl From<UserId> for Uuid {
@@ -153,19 +411,26 @@ impl From<UserId> for Uuid {
     }
 }
 
-impl UserId {
-    pub fn my_to_String(&self) -> String {
-        self.0.to_string()
+/// A `Uuid` is always a valid id, used for ids read back from storage or freshly generated.
+impl From<Uuid> for UserId {
+    fn from(uuid: Uuid) -> Self {
+        Self(uuid)
+    }
+}
+
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
     }
 }
 
 impl TryFrom<String> for UserId {
-    type Error = ();
+    type Error = UserIdError;
 
     fn try_from(s: String) -> Result<Self, Self::Error> {
         match Uuid::parse_str(s.as_str()) {
             Ok(uuid) => { Ok(Self(uuid)) }
-            Err(_) => { Err(()) }
+            Err(_) => { Err(UserIdError::Malformed) }
         }
     }
 }
@@ -179,4 +444,299 @@ impl UserId {
     pub fn bad() -> Self {
         Self("".parse().unwrap())
     }
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WebhookId(Uuid);
+
+impl From<WebhookId> for Uuid {
+    fn from(t: WebhookId) -> Self {
+        t.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookIdError {
+    Malformed,
+}
+
+impl ValidationCode for WebhookIdError {
+    fn code(&self) -> &'static str {
+        match self {
+            WebhookIdError::Malformed => "malformed",
+        }
+    }
+}
+
+impl TryFrom<String> for WebhookId {
+    type Error = WebhookIdError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Uuid::parse_str(s.as_str()).map(Self).map_err(|_| WebhookIdError::Malformed)
+    }
+}
+
+pub const WEBHOOK_URL_MAX_LENGTH: usize = 2048;
+
+/// Where webhooks may be posted, built from the configuration in `main`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WebhookRules {
+    /// Plain `http` is refused unless set, for receivers on a trusted network.
+    pub allow_http: bool,
+}
+
+/// Whether a webhook may reach `ip`: a subscriber must not make the service call itself,
+/// the cloud metadata endpoint or anything else on the private network.
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+                || ip.is_broadcast() || ip.is_multicast() || ip.is_documentation()
+                // 0.0.0.0/8 and the carrier-grade NAT 100.64.0.0/10
+                || a == 0 || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_address(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast()
+                    // unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Absolute `https` address a webhook is posted to, `http` when the rules allow it.
+/// Literal private addresses and `localhost` are refused, names are checked again once resolved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebhookUrl(String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookUrlError {
+    Invalid,
+    TooLong,
+    HttpsRequired,
+    PrivateAddress,
+}
+
+impl ValidationCode for WebhookUrlError {
+    fn code(&self) -> &'static str {
+        match self {
+            WebhookUrlError::Invalid => "invalid_url",
+            WebhookUrlError::TooLong => "too_long",
+            WebhookUrlError::HttpsRequired => "https_required",
+            WebhookUrlError::PrivateAddress => "private_address",
+        }
+    }
+}
+
+impl From<WebhookUrl> for String {
+    fn from(t: WebhookUrl) -> Self {
+        t.0
+    }
+}
+
+impl WebhookUrl {
+    pub fn parse(s: &str, rules: &WebhookRules) -> Result<Self, WebhookUrlError> {
+        let s = s.trim();
+        if s.len() > WEBHOOK_URL_MAX_LENGTH {
+            return Err(WebhookUrlError::TooLong);
+        }
+        let url = Url::parse(s).map_err(|_| WebhookUrlError::Invalid)?;
+        match url.scheme() {
+            "https" => {}
+            "http" if rules.allow_http => {}
+            "http" => return Err(WebhookUrlError::HttpsRequired),
+            _ => return Err(WebhookUrlError::Invalid),
+        }
+        // the parser already turned `0x7f.1` and the like into addresses
+        let public = match url.host() {
+            Some(Host::Ipv4(ip)) => is_public_address(IpAddr::V4(ip)),
+            Some(Host::Ipv6(ip)) => is_public_address(IpAddr::V6(ip)),
+            Some(Host::Domain(domain)) => {
+                let domain = domain.trim_end_matches('.');
+                domain != "localhost" && !domain.ends_with(".localhost")
+            }
+            None => return Err(WebhookUrlError::Invalid),
+        };
+        if !public {
+            return Err(WebhookUrlError::PrivateAddress);
+        }
+        Ok(Self(url.to_string()))
+    }
+}
+
+/// Event types a webhook listens to, empty for all of them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventFilter(Vec<String>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFilterError {
+    UnknownEvent,
+}
+
+impl ValidationCode for EventFilterError {
+    fn code(&self) -> &'static str {
+        match self {
+            EventFilterError::UnknownEvent => "unknown_event",
+        }
+    }
+}
+
+impl From<EventFilter> for Vec<String> {
+    fn from(t: EventFilter) -> Self {
+        t.0
+    }
+}
+
+impl TryFrom<Vec<String>> for EventFilter {
+    type Error = EventFilterError;
+
+    fn try_from(events: Vec<String>) -> Result<Self, Self::Error> {
+        let mut filter: Vec<String> = Vec::new();
+        for event in events {
+            if !UserEvent::TYPES.contains(&event.as_str()) {
+                return Err(EventFilterError::UnknownEvent);
+            }
+            if !filter.contains(&event) {
+                filter.push(event);
+            }
+        }
+        Ok(Self(filter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::clock::FixedClock;
+
+    #[test]
+    fn text_errors_are_typed() {
+        let rules = TextRules::default();
+        assert_eq!(FirstName::parse("", &rules).err(), Some(TextError::Empty));
+        assert_eq!(FirstName::parse("   ", &rules).err(), Some(TextError::Empty));
+        assert_eq!(LastName::parse(&"a".repeat(DEFAULT_MAX_LENGTH + 1), &rules).err(), Some(TextError::TooLong));
+        assert_eq!(LastName::parse("muf2", &rules).err(), Some(TextError::InvalidCharacters));
+        assert_eq!(CityName::parse("ni\nce", &rules).err(), Some(TextError::InvalidCharacters));
+        assert!(CityName::parse("nice", &rules).is_ok());
+    }
+
+    #[test]
+    fn text_is_normalized() {
+        let rules = TextRules::default();
+        let name = FirstName::parse("  Jean   Pierre ", &rules).unwrap();
+        assert_eq!(String::from(name), "Jean Pierre");
+        // "e" followed by a combining acute accent is composed into "é"
+        let name = LastName::parse("Jose\u{301}", &rules).unwrap();
+        assert_eq!(String::from(name), "Jos\u{e9}");
+        for city in ["  paris ", "PARIS", "Paris"] {
+            assert_eq!(String::from(CityName::parse(city, &rules).unwrap()), "Paris");
+        }
+        let city = CityName::parse("saint-ÉTIENNE", &rules).unwrap();
+        assert_eq!(String::from(city), "Saint-Étienne");
+        assert_eq!(String::from(CityName::parse("aix 2", &rules).unwrap()), "Aix 2");
+    }
+
+    #[test]
+    fn text_rules_are_configurable() {
+        let rules = TextRules { name_max_length: 3, city_max_length: 4 };
+        assert!(FirstName::parse("Zoé", &rules).is_ok());
+        assert_eq!(FirstName::parse("Hugo", &rules).err(), Some(TextError::TooLong));
+        assert_eq!(CityName::parse("Paris", &rules).err(), Some(TextError::TooLong));
+    }
+
+    #[test]
+    fn birthday_date_errors_are_typed() {
+        let rules = BirthdayRules::default();
+        let clock = FixedClock(NaiveDate::from_ymd_opt(2022, 10, 3).unwrap());
+        assert_eq!(BirthdayDate::parse("03-10-1994", &rules, &clock, None).err(), Some(BirthdayDateError::InvalidFormat));
+        assert_eq!(BirthdayDate::parse("2022-10-04", &rules, &clock, None).err(), Some(BirthdayDateError::InFuture));
+    }
+
+    #[test]
+    fn birthday_rules_use_the_clock() {
+        let clock = FixedClock(NaiveDate::from_ymd_opt(2022, 10, 3).unwrap());
+        let rules = BirthdayRules { min_year: 1900, min_age: 18, ..BirthdayRules::default() };
+        assert!(BirthdayDate::parse("2004-10-03", &rules, &clock, None).is_ok());
+        assert_eq!(BirthdayDate::parse("2004-10-04", &rules, &clock, None).err(), Some(BirthdayDateError::TooYoung));
+        assert_eq!(BirthdayDate::parse("2022-10-04", &rules, &clock, None).err(), Some(BirthdayDateError::InFuture));
+        assert_eq!(BirthdayDate::parse("0001-01-01", &rules, &clock, None).err(), Some(BirthdayDateError::TooOld));
+    }
+
+    #[test]
+    fn birthday_date_accepts_every_configured_format() {
+        let clock = FixedClock(NaiveDate::from_ymd_opt(2022, 10, 3).unwrap());
+        let rules = BirthdayRules::default();
+        let expected = NaiveDate::from_ymd_opt(1994, 10, 3).unwrap();
+        for input in ["1994-10-03", "1994-10-03T00:00:00Z", "1994-10-03T23:30:00-05:00", "03/10/1994", "03.10.1994", "3 Oct 1994"] {
+            let date = BirthdayDate::parse(input, &rules, &clock, None).unwrap();
+            assert_eq!(NaiveDate::from(date), expected, "{}", input);
+        }
+        let date = BirthdayDate::parse("10/03/1994", &rules, &clock, Some(DateOrder::MonthDay)).unwrap();
+        assert_eq!(NaiveDate::from(date), expected);
+        let iso_only = BirthdayRules { formats: vec![DateFormat::Iso], ..BirthdayRules::default() };
+        assert_eq!(BirthdayDate::parse("03/10/1994", &iso_only, &clock, None).err(), Some(BirthdayDateError::InvalidFormat));
+    }
+
+    #[test]
+    fn date_formats_must_read_a_whole_date() {
+        assert_eq!("numeric".parse(), Ok(DateFormat::Numeric));
+        assert_eq!("%d %b %Y".parse(), Ok(DateFormat::Pattern(String::from("%d %b %Y"))));
+        for invalid in ["", " ", "%Q", "%d/%m", "dd/mm/yyyy"] {
+            assert_eq!(invalid.parse::<DateFormat>(), Err(()), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn age_and_next_birthday() {
+        let birthday = NaiveDate::from_ymd_opt(1994, 10, 3).unwrap();
+        assert_eq!(age(birthday, NaiveDate::from_ymd_opt(2022, 10, 2).unwrap()), 27);
+        assert_eq!(age(birthday, NaiveDate::from_ymd_opt(2022, 10, 3).unwrap()), 28);
+        assert_eq!(next_birthday(birthday, NaiveDate::from_ymd_opt(2022, 10, 3).unwrap()), NaiveDate::from_ymd_opt(2022, 10, 3).unwrap());
+        assert_eq!(next_birthday(birthday, NaiveDate::from_ymd_opt(2022, 10, 4).unwrap()), NaiveDate::from_ymd_opt(2023, 10, 3).unwrap());
+        let leap = NaiveDate::from_ymd_opt(2000, 2, 29).unwrap();
+        assert_eq!(age(leap, NaiveDate::from_ymd_opt(2021, 2, 28).unwrap()), 21);
+        assert_eq!(next_birthday(leap, NaiveDate::from_ymd_opt(2021, 1, 1).unwrap()), NaiveDate::from_ymd_opt(2021, 2, 28).unwrap());
+    }
+
+    #[test]
+    fn validation_errors_aggregate_every_field() {
+        let mut errors = ValidationErrors::new();
+        let first_name = errors.check("first_name", FirstName::parse("", &TextRules::default()));
+        let birthday_date = errors.check("birthday_date", BirthdayDate::parse("1994/10/03", &BirthdayRules::default(), &FixedClock(NaiveDate::from_ymd_opt(2022, 10, 3).unwrap()), None));
+        let city = errors.check("city", CityName::parse("nice", &TextRules::default()));
+        assert!(first_name.is_none() && birthday_date.is_none() && city.is_some());
+        assert_eq!(errors.0, vec![
+            FieldError { field: "first_name", code: "empty" },
+            FieldError { field: "birthday_date", code: "invalid_format" },
+        ]);
+    }
+
+    #[test]
+    fn webhook_entities_are_validated() {
+        let rules = WebhookRules::default();
+        assert_eq!(String::from(WebhookUrl::parse(" https://partner.example/hooks ", &rules).unwrap()), "https://partner.example/hooks");
+        assert_eq!(WebhookUrl::parse("http://partner.example/hooks", &rules).err(), Some(WebhookUrlError::HttpsRequired));
+        assert!(WebhookUrl::parse("http://partner.example/hooks", &WebhookRules { allow_http: true }).is_ok());
+        assert_eq!(WebhookUrl::parse("ftp://partner.example", &rules).err(), Some(WebhookUrlError::Invalid));
+        assert_eq!(WebhookUrl::parse("https://", &rules).err(), Some(WebhookUrlError::Invalid));
+        assert_eq!(WebhookUrl::parse("https://a b", &rules).err(), Some(WebhookUrlError::Invalid));
+        assert_eq!(WebhookUrl::parse(&format!("https://{}", "a".repeat(WEBHOOK_URL_MAX_LENGTH)), &rules).err(), Some(WebhookUrlError::TooLong));
+        for private in [
+            "https://localhost:9000", "https://api.localhost", "https://127.0.0.1", "https://0x7f.1", "https://10.0.0.8",
+            "https://192.168.1.1", "https://169.254.169.254/latest/meta-data", "https://0.0.0.0", "https://[::1]",
+            "https://[::]", "https://[fd00::1]", "https://[fe80::1]", "https://[::ffff:127.0.0.1]",
+        ] {
+            assert_eq!(WebhookUrl::parse(private, &rules).err(), Some(WebhookUrlError::PrivateAddress), "{}", private);
+        }
+        assert!(WebhookUrl::parse("https://93.184.216.34/hooks", &rules).is_ok());
+        assert!(WebhookUrl::parse("https://[2606:2800:220:1:248:1893:25c8:1946]/hooks", &rules).is_ok());
+        let filter = EventFilter::try_from(vec![String::from("UserCreated"), String::from("UserCreated")]).unwrap();
+        assert_eq!(Vec::from(filter), vec![String::from("UserCreated")]);
+        assert_eq!(EventFilter::try_from(vec![String::from("user.created")]).err(), Some(EventFilterError::UnknownEvent));
+        assert_eq!(WebhookId::try_from(String::from("nope")).err(), Some(WebhookIdError::Malformed));
+    }
+}
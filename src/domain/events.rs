@@ -0,0 +1,331 @@
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::repository::history::ChangeContext;
+use crate::repository::user::DbUser;
+
+/// Something that happened to a user, published once the change is stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[allow(clippy::enum_variant_names)]
+pub enum UserEvent {
+    UserCreated(UserCreated),
+    UserUpdated(UserUpdated),
+    UserDeleted(UserDeleted),
+    UserRestored(UserRestored),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserCreated {
+    pub user_id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub birthday_date: NaiveDate,
+    pub city: String,
+    pub version: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: String,
+    pub request_id: String,
+}
+
+/// Only the fields whose value changed, an update writing the same values has none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserUpdated {
+    pub user_id: Uuid,
+    /// City after the update, absent from events stored before it was added.
+    #[serde(default)]
+    pub city: String,
+    pub version: i64,
+    pub changes: Vec<FieldChange>,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: String,
+    pub request_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserDeleted {
+    pub user_id: Uuid,
+    #[serde(default)]
+    pub city: String,
+    pub version: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: String,
+    pub request_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserRestored {
+    pub user_id: Uuid,
+    #[serde(default)]
+    pub city: String,
+    pub version: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: String,
+    pub request_id: String,
+}
+
+impl UserEvent {
+    pub fn created(user: &DbUser, context: &ChangeContext) -> Self {
+        UserEvent::UserCreated(UserCreated {
+            user_id: user.id,
+            first_name: user.first_name.clone(),
+            last_name: user.last_name.clone(),
+            birthday_date: user.birthday_date,
+            city: user.city.clone(),
+            version: user.version,
+            occurred_at: user.created_at,
+            actor: context.actor.clone(),
+            request_id: context.request_id.clone(),
+        })
+    }
+
+    pub fn updated(old: &DbUser, new: &DbUser, context: &ChangeContext) -> Self {
+        let mut changes = vec![];
+        let mut compare = |field: &str, old: String, new: String| {
+            if old != new {
+                changes.push(FieldChange { field: field.to_string(), old, new });
+            }
+        };
+        compare("first_name", old.first_name.clone(), new.first_name.clone());
+        compare("last_name", old.last_name.clone(), new.last_name.clone());
+        compare("birthday_date", old.birthday_date.to_string(), new.birthday_date.to_string());
+        compare("city", old.city.clone(), new.city.clone());
+        UserEvent::UserUpdated(UserUpdated {
+            user_id: new.id,
+            city: new.city.clone(),
+            version: new.version,
+            changes,
+            occurred_at: new.updated_at,
+            actor: context.actor.clone(),
+            request_id: context.request_id.clone(),
+        })
+    }
+
+    pub fn deleted(user_id: Uuid, city: &str, version: i64, occurred_at: DateTime<Utc>, context: &ChangeContext) -> Self {
+        UserEvent::UserDeleted(UserDeleted {
+            user_id,
+            city: city.to_string(),
+            version,
+            occurred_at,
+            actor: context.actor.clone(),
+            request_id: context.request_id.clone(),
+        })
+    }
+
+    pub fn restored(user: &DbUser, context: &ChangeContext) -> Self {
+        UserEvent::UserRestored(UserRestored {
+            user_id: user.id,
+            city: user.city.clone(),
+            version: user.version,
+            occurred_at: user.updated_at,
+            actor: context.actor.clone(),
+            request_id: context.request_id.clone(),
+        })
+    }
+
+    /// Every value of `event_type`, which is also the `type` field of the serialized event.
+    pub const TYPES: [&'static str; 4] = ["UserCreated", "UserUpdated", "UserDeleted", "UserRestored"];
+
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            UserEvent::UserCreated(_) => "UserCreated",
+            UserEvent::UserUpdated(_) => "UserUpdated",
+            UserEvent::UserDeleted(_) => "UserDeleted",
+            UserEvent::UserRestored(_) => "UserRestored",
+        }
+    }
+
+    pub fn user_id(&self) -> Uuid {
+        match self {
+            UserEvent::UserCreated(event) => event.user_id,
+            UserEvent::UserUpdated(event) => event.user_id,
+            UserEvent::UserDeleted(event) => event.user_id,
+            UserEvent::UserRestored(event) => event.user_id,
+        }
+    }
+}
+
+/// Port the use cases hand their events to, they do not know who listens.
+pub trait EventPublisher: Send + Sync {
+    fn publish(&self, event: UserEvent);
+
+    /// Another instance purged deleted users, which publishes no event per user.
+    fn publish_purge(&self);
+
+    /// Changes of other instances may have been missed, while the change feed was down.
+    fn publish_resync(&self);
+}
+
+/// Reacts to user events in the same process, cache invalidation or search indexing for instance.
+/// Runs on the request path, so long work belongs in a spawned task.
+pub trait Subscriber: Send + Sync {
+    fn handle(&self, event: &UserEvent);
+
+    /// Deleted users were purged by another instance, what was kept about them is gone.
+    fn purged(&self) {}
+
+    /// Events of other instances may have been lost, what was derived from them cannot be trusted.
+    fn resync(&self) {}
+}
+
+/// Registry delivering every published event to each subscriber, in subscription order.
+pub struct EventBus {
+    subscribers: RwLock<Vec<Arc<dyn Subscriber>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus { subscribers: RwLock::new(vec![]) }
+    }
+
+    pub fn subscribe(&self, subscriber: Arc<dyn Subscriber>) {
+        self.subscribers.write().unwrap().push(subscriber);
+    }
+}
+
+impl EventPublisher for EventBus {
+    fn publish(&self, event: UserEvent) {
+        for subscriber in self.subscribers.read().unwrap().iter() {
+            subscriber.handle(&event);
+        }
+    }
+
+    fn publish_purge(&self) {
+        for subscriber in self.subscribers.read().unwrap().iter() {
+            subscriber.purged();
+        }
+    }
+    fn publish_resync(&self) {
+        for subscriber in self.subscribers.read().unwrap().iter() {
+            subscriber.resync();
+        }
+    }
+}
+
+/// Writes each event to the logs.
+pub struct LogSubscriber;
+
+impl Subscriber for LogSubscriber {
+    fn handle(&self, event: &UserEvent) {
+        tracing::info!(user_id = %event.user_id(), "{:?}", event);
+    }
+}
+
+/// Keeps every event it receives, to assert on them in tests.
+#[cfg(test)]
+pub struct RecordingSubscriber {
+    pub events: RwLock<Vec<UserEvent>>,
+    pub purges: RwLock<usize>,
+    pub resyncs: RwLock<usize>,
+}
+
+#[cfg(test)]
+impl RecordingSubscriber {
+    pub fn new() -> Self {
+        RecordingSubscriber { events: RwLock::new(vec![]), purges: RwLock::new(0), resyncs: RwLock::new(0) }
+    }
+
+    pub fn events(&self) -> Vec<UserEvent> {
+        self.events.read().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Subscriber for RecordingSubscriber {
+    fn handle(&self, event: &UserEvent) {
+        self.events.write().unwrap().push(event.clone());
+    }
+
+    fn purged(&self) {
+        *self.purges.write().unwrap() += 1;
+    }
+
+    fn resync(&self) {
+        *self.resyncs.write().unwrap() += 1;
+    }
+}
+
+#[cfg(test)]
+impl EventPublisher for RecordingSubscriber {
+    fn publish(&self, event: UserEvent) {
+        self.handle(&event);
+    }
+
+    fn publish_purge(&self) {
+        self.purged();
+    }
+
+    fn publish_resync(&self) {
+        self.resync();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use chrono::{NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::domain::events::{EventBus, EventPublisher, FieldChange, RecordingSubscriber, UserEvent};
+    use crate::repository::history::ChangeContext;
+    use crate::repository::user::DbUser;
+
+    fn user() -> DbUser {
+        DbUser {
+            id: Uuid::new_v4(),
+            first_name: "Hugo".to_string(),
+            last_name: "Muf".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            city: "Nice".to_string(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn event_bus_delivers_to_every_subscriber() {
+        let bus = EventBus::new();
+        let first = Arc::new(RecordingSubscriber::new());
+        let second = Arc::new(RecordingSubscriber::new());
+        bus.subscribe(first.clone());
+        bus.subscribe(second.clone());
+        let event = UserEvent::created(&user(), &ChangeContext::test());
+        bus.publish(event.clone());
+        assert_eq!(first.events(), vec![event.clone()]);
+        assert_eq!(second.events(), vec![event]);
+    }
+
+    #[test]
+    fn updated_lists_changed_fields() {
+        let old = user();
+        let new = DbUser { city: "Paris".to_string(), birthday_date: NaiveDate::from_ymd_opt(2015, 3, 15).unwrap(), version: 2, ..old.clone() };
+        match UserEvent::updated(&old, &new, &ChangeContext::test()) {
+            UserEvent::UserUpdated(event) => {
+                assert_eq!(event.version, 2);
+                assert_eq!(event.changes, vec![
+                    FieldChange { field: "birthday_date".to_string(), old: "2015-03-14".to_string(), new: "2015-03-15".to_string() },
+                    FieldChange { field: "city".to_string(), old: "Nice".to_string(), new: "Paris".to_string() },
+                ]);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn event_serializes_with_type() {
+        let event = UserEvent::deleted(Uuid::nil(), "Nice", 3, Utc::now(), &ChangeContext::test());
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "UserDeleted");
+        assert_eq!(json["type"], event.event_type());
+        assert_eq!(json["version"], 3);
+    }
+}
@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use uuid::Uuid;
+use crate::domain::entities::UserId;
+
+/// Port handing out the id of every new user.
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> UserId;
+}
+
+/// Random ids.
+pub struct UuidV4Generator;
+
+impl IdGenerator for UuidV4Generator {
+    fn generate(&self) -> UserId {
+        UserId::from(Uuid::new_v4())
+    }
+}
+
+/// Ids starting with a millisecond timestamp, new rows land at the end of the primary key
+/// index and sorting by id follows creation order.
+pub struct UuidV7Generator;
+
+impl IdGenerator for UuidV7Generator {
+    fn generate(&self) -> UserId {
+        UserId::from(Uuid::now_v7())
+    }
+}
+
+/// `00000000-0000-0000-0000-000000000001`, then `...002` and so on, for tests and fixtures.
+/// Restarts from 1 with the process, so only meant for the in-memory storage.
+pub struct SequentialIdGenerator {
+    next: AtomicU64,
+}
+
+impl SequentialIdGenerator {
+    pub fn new() -> Self {
+        SequentialIdGenerator { next: AtomicU64::new(1) }
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn generate(&self) -> UserId {
+        UserId::from(Uuid::from_u128(self.next.fetch_add(1, Ordering::SeqCst) as u128))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::{uuid, Uuid};
+    use crate::domain::id_generator::{IdGenerator, SequentialIdGenerator, UuidV4Generator, UuidV7Generator};
+
+    #[test]
+    fn sequential_is_deterministic() {
+        let ids = SequentialIdGenerator::new();
+        assert_eq!(Uuid::from(ids.generate()), uuid!("00000000-0000-0000-0000-000000000001"));
+        assert_eq!(Uuid::from(ids.generate()), uuid!("00000000-0000-0000-0000-000000000002"));
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(Uuid::from(UuidV4Generator.generate()).get_version_num(), 4);
+        let first = Uuid::from(UuidV7Generator.generate());
+        let second = Uuid::from(UuidV7Generator.generate());
+        assert_eq!(first.get_version_num(), 7);
+        assert!(first < second);
+    }
+}
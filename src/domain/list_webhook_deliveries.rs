@@ -0,0 +1,94 @@
+use uuid::Uuid;
+use crate::domain::entities::{ValidationErrors, WebhookId};
+use crate::domain::user_history::check_range;
+use crate::repository::webhook::{DbDelivery, DbDeliveryPage, WebhookError, WebhookRepository};
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Default)]
+pub struct Request {
+    pub id: String,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct Page {
+    pub deliveries: Vec<DbDelivery>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub next_offset: Option<i64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    BadRequest(ValidationErrors),
+    NotFound,
+    Unknown,
+}
+
+pub async fn execute(repo: &dyn WebhookRepository, req: Request) -> Result<Page, Error> {
+    let mut errors = ValidationErrors::new();
+    let (id, limit, offset) = match (
+        errors.check("id", WebhookId::try_from(req.id)),
+        errors.check("limit", check_range(req.limit.unwrap_or(DEFAULT_LIMIT), 1, MAX_LIMIT)),
+        errors.check("offset", check_range(req.offset.unwrap_or(0), 0, i64::MAX)),
+    ) {
+        (Some(id), Some(limit), Some(offset)) => (Uuid::from(id), limit, offset),
+        _ => return Err(Error::BadRequest(errors)),
+    };
+    match repo.get_webhook(id).await {
+        Ok(_) => {}
+        Err(WebhookError::NotFound) => return Err(Error::NotFound),
+        Err(WebhookError::Unknown) => return Err(Error::Unknown),
+    }
+    match repo.fetch_deliveries(id, limit, offset).await {
+        Ok(DbDeliveryPage { deliveries, total }) => {
+            let next_offset = if offset + limit < total { Some(offset + limit) } else { None };
+            Ok(Page { deliveries, total, limit, offset, next_offset })
+        }
+        Err(_) => Err(Error::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+    use uuid::Uuid;
+    use crate::domain::list_webhook_deliveries::{execute, Error, Request};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::webhook::{DbWebhook, DeliveryStatus, WebhookRepository};
+
+    #[tokio::test]
+    async fn list_webhook_deliveries_domain_works() {
+        let repo = InMemoryRepository::new();
+        let webhook = repo.insert_webhook(DbWebhook {
+            id: Uuid::new_v4(),
+            url: String::from("https://partner.example/hooks"),
+            events: vec![String::from("UserCreated")],
+            secret: String::from("whsec_test"),
+            active: true,
+            consecutive_failures: 0,
+            disabled_at: None,
+            created_at: Utc::now(),
+        }).await.unwrap();
+        repo.queue_deliveries(1, "UserCreated", &json!({"id": 1})).await.unwrap();
+        repo.queue_deliveries(2, "UserDeleted", &json!({"id": 2})).await.unwrap();
+        repo.queue_deliveries(3, "UserCreated", &json!({"id": 3})).await.unwrap();
+        let res = execute(&repo, Request { id: webhook.id.to_string(), limit: Some(1), ..Request::default() }).await.unwrap();
+        assert_eq!(res.total, 2);
+        assert_eq!(res.next_offset, Some(1));
+        assert_eq!(res.deliveries[0].message_id, 3);
+        assert_eq!(res.deliveries[0].status, DeliveryStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn list_webhook_deliveries_domain_not_found() {
+        let repo = InMemoryRepository::new();
+        let res = execute(&repo, Request { id: Uuid::new_v4().to_string(), ..Request::default() }).await;
+        assert_eq!(res.err().unwrap(), Error::NotFound);
+    }
+}
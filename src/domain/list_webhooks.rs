@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::repository::webhook::{DbWebhook, WebhookRepository};
+
+/// A subscription without its secret.
+#[derive(Debug)]
+pub struct Response {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub consecutive_failures: i32,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Unknown,
+}
+
+pub async fn execute(repo: &dyn WebhookRepository) -> Result<Vec<Response>, Error> {
+    match repo.fetch_webhooks().await {
+        Ok(webhooks) => Ok(webhooks.into_iter()
+            .map(|DbWebhook { id, url, events, active, consecutive_failures, disabled_at, created_at, .. }| Response {
+                id,
+                url,
+                events,
+                active,
+                consecutive_failures,
+                disabled_at,
+                created_at,
+            })
+            .collect()),
+        Err(_) => Err(Error::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::domain::list_webhooks::execute;
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::webhook::{DbWebhook, WebhookRepository};
+
+    #[tokio::test]
+    async fn list_webhooks_domain_works() {
+        let repo = InMemoryRepository::new();
+        assert!(execute(&repo).await.unwrap().is_empty());
+        let webhook = repo.insert_webhook(DbWebhook {
+            id: Uuid::new_v4(),
+            url: String::from("https://partner.example/hooks"),
+            events: vec![],
+            secret: String::from("whsec_test"),
+            active: true,
+            consecutive_failures: 0,
+            disabled_at: None,
+            created_at: Utc::now(),
+        }).await.unwrap();
+        let res = execute(&repo).await.unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].id, webhook.id);
+        assert_eq!(res[0].url, webhook.url);
+    }
+}
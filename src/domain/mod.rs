@@ -4,4 +4,14 @@ pub mod get_user;
 pub mod delete_user;
 pub mod list_users;
 pub mod update_user;
-
+pub mod patch_user;pub mod clock;
+pub mod rules;
+pub mod id_generator;
+pub mod restore_user;
+pub mod purge_users;
+pub mod user_history;
+pub mod events;
+pub mod create_webhook;
+pub mod list_webhooks;
+pub mod delete_webhook;
+pub mod list_webhook_deliveries;
@@ -0,0 +1,421 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+use crate::domain::entities::{BirthdayDate, CityName, DateOrder, FirstName, LastName, UserId, ValidationCode, ValidationErrors};
+use crate::domain::events::{EventPublisher, UserEvent};
+use crate::domain::rules::UserRules;
+use crate::repository::user::{DbUser, FetchOneError, Repository, UpdateError};
+use crate::repository::history::ChangeContext;
+
+/// Fields left to `None` keep their current value.
+#[derive(Debug, Default)]
+pub struct Request {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub birthday_date: Option<String>,
+    pub city: Option<String>,
+    /// How to read numeric birthdays, the configured order when `None`.
+    pub date_order: Option<DateOrder>,
+}
+
+#[derive(Debug)]
+pub struct Params {
+    pub id: String,
+    pub version: i64,
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub birthday_date: NaiveDate,
+    pub city: String,
+    pub version: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// RFC 6902 operation on the user document, `path` is a JSON pointer such as `/city`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operation {
+    Add { path: String, value: String },
+    Replace { path: String, value: String },
+    Remove { path: String },
+    Test { path: String, value: String },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct OperationError {
+    pub index: Option<usize>,
+    pub path: String,
+    pub reason: &'static str,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    BadRequest(ValidationErrors),
+    NotFound,
+    VersionMismatch,
+    Unknown,
+    TestFailed(OperationError),
+    Unprocessable(OperationError),
+}
+
+fn validate<T, E: ValidationCode>(errors: &mut ValidationErrors, field: &'static str, value: Option<String>, parse: impl FnOnce(&str) -> Result<T, E>) -> Option<T> {
+    value.and_then(|value| errors.check(field, parse(&value)))
+}
+
+fn code<T, E: ValidationCode>(result: Result<T, E>) -> Option<&'static str> {
+    result.err().map(|error| error.code())
+}
+
+/// Value of a field while operations are applied.
+#[derive(Debug, PartialEq)]
+enum Value {
+    /// Untouched, kept as stored without being read nor validated again.
+    Stored(String),
+    Changed(String),
+    Removed,
+}
+
+impl Value {
+    fn current(&self) -> Option<&String> {
+        match self {
+            Value::Stored(value) | Value::Changed(value) => Some(value),
+            Value::Removed => None,
+        }
+    }
+}
+
+/// Working copy of the user while operations are applied.
+struct Document {
+    id: String,
+    fields: [(&'static str, Value); 4],
+}
+
+impl Document {
+    fn new(user: DbUser) -> Self {
+        Document {
+            id: user.id.to_string(),
+            fields: [
+                ("/first_name", Value::Stored(user.first_name)),
+                ("/last_name", Value::Stored(user.last_name)),
+                // only what `test` compares against
+                ("/birthday_date", Value::Stored(user.birthday_date.format("%Y-%m-%d").to_string())),
+                ("/city", Value::Stored(user.city)),
+            ],
+        }
+    }
+
+    fn field(&mut self, index: usize, path: &str) -> Result<&mut Value, Error> {
+        if path == "/id" {
+            return Err(unprocessable(Some(index), path, "id is read only"));
+        }
+        match self.fields.iter_mut().find(|(name, _)| *name == path) {
+            Some((_, value)) => Ok(value),
+            None => Err(unprocessable(Some(index), path, "unknown path")),
+        }
+    }
+
+    fn apply(&mut self, index: usize, operation: Operation) -> Result<(), Error> {
+        match operation {
+            Operation::Test { path, value } => {
+                let current = if path == "/id" { Some(self.id.clone()) } else { self.field(index, &path)?.current().cloned() };
+                if current.as_ref() == Some(&value) {
+                    Ok(())
+                } else {
+                    Err(Error::TestFailed(OperationError { index: Some(index), path, reason: "test failed" }))
+                }
+            }
+            Operation::Add { path, value } => {
+                *self.field(index, &path)? = Value::Changed(value);
+                Ok(())
+            }
+            Operation::Replace { path, value } => {
+                let field = self.field(index, &path)?;
+                if *field == Value::Removed {
+                    return Err(unprocessable(Some(index), &path, "path does not exist"));
+                }
+                *field = Value::Changed(value);
+                Ok(())
+            }
+            Operation::Remove { path } => {
+                let field = self.field(index, &path)?;
+                if *field == Value::Removed {
+                    return Err(unprocessable(Some(index), &path, "path does not exist"));
+                }
+                *field = Value::Removed;
+                Ok(())
+            }
+        }
+    }
+
+    /// The merge patch of the changed fields, validated against the current rules. The others
+    /// are left out and keep their stored value, even if the rules changed since it was written.
+    fn into_request(self, rules: &UserRules, date_order: Option<DateOrder>) -> Result<Request, Error> {
+        let mut req = Request { date_order, ..Request::default() };
+        for (path, value) in self.fields {
+            let value = match value {
+                Value::Stored(_) => continue,
+                Value::Changed(value) => value,
+                Value::Removed => return Err(unprocessable(None, path, "field is required")),
+            };
+            let invalid = match path {
+                "/first_name" => code(FirstName::parse(&value, &rules.text)),
+                "/last_name" => code(LastName::parse(&value, &rules.text)),
+                "/birthday_date" => code(BirthdayDate::parse(&value, &rules.birthday, rules.clock.as_ref(), date_order)),
+                _ => code(CityName::parse(&value, &rules.text)),
+            };
+            if let Some(reason) = invalid {
+                return Err(unprocessable(None, path, reason));
+            }
+            match path {
+                "/first_name" => req.first_name = Some(value),
+                "/last_name" => req.last_name = Some(value),
+                "/birthday_date" => req.birthday_date = Some(value),
+                _ => req.city = Some(value),
+            }
+        }
+        Ok(req)
+    }
+}
+
+fn unprocessable(index: Option<usize>, path: &str, reason: &'static str) -> Error {
+    Error::Unprocessable(OperationError { index, path: path.to_string(), reason })
+}
+
+/// Applies every operation to a copy of the stored user and only writes it
+/// back once all of them, `test` included, succeeded and the result is valid.
+pub async fn execute_json_patch(repo: &dyn Repository, events: &dyn EventPublisher, rules: &UserRules, context: &ChangeContext, params: Params, operations: Vec<Operation>, date_order: Option<DateOrder>) -> Result<Response, Error> {
+    let id = UserId::try_from(params.id).map_err(|error| Error::BadRequest(ValidationErrors::single("id", error)))?;
+    let mut document = match repo.get(id).await {
+        // `test` operations must run against the version the client saw
+        Ok(user) if user.version != params.version => return Err(Error::VersionMismatch),
+        Ok(user) => Document::new(user),
+        Err(FetchOneError::NotFound) => return Err(Error::NotFound),
+        Err(FetchOneError::Unknown) => return Err(Error::Unknown),
+    };
+    for (index, operation) in operations.into_iter().enumerate() {
+        document.apply(index, operation)?;
+    }
+    let req = document.into_request(rules, date_order)?;
+    execute(repo, events, rules, context, Params { id: id.to_string(), version: params.version }, req).await
+}
+
+pub async fn execute(repo: &dyn Repository, events: &dyn EventPublisher, rules: &UserRules, context: &ChangeContext, params: Params, req: Request) -> Result<Response, Error> {
+    let mut errors = ValidationErrors::new();
+    let id = errors.check("id", UserId::try_from(params.id));
+    let first_name = validate(&mut errors, "first_name", req.first_name, |value| FirstName::parse(value, &rules.text));
+    let last_name = validate(&mut errors, "last_name", req.last_name, |value| LastName::parse(value, &rules.text));
+    let birthday_date = validate(&mut errors, "birthday_date", req.birthday_date, |value| BirthdayDate::parse(value, &rules.birthday, rules.clock.as_ref(), req.date_order));
+    let city = validate(&mut errors, "city", req.city, |value| CityName::parse(value, &rules.text));
+    let id = match id {
+        Some(id) if errors.is_empty() => id,
+        _ => return Err(Error::BadRequest(errors)),
+    };
+
+    let current = match repo.get(id).await {
+        // the fields left out are taken from it, it must be the version the client changes
+        Ok(user) if user.version != params.version => return Err(Error::VersionMismatch),
+        Ok(user) => user,
+        Err(FetchOneError::NotFound) => return Err(Error::NotFound),
+        Err(FetchOneError::Unknown) => return Err(Error::Unknown),
+    };
+    let old = current.clone();
+    let res = repo.update(id, DbUser {
+        id: Uuid::from(id),
+        first_name: first_name.map(String::from).unwrap_or(current.first_name),
+        last_name: last_name.map(String::from).unwrap_or(current.last_name),
+        birthday_date: birthday_date.map(NaiveDate::from).unwrap_or(current.birthday_date),
+        city: city.map(String::from).unwrap_or(current.city),
+        version: params.version,
+        created_at: current.created_at,
+        updated_at: current.updated_at,
+        deleted_at: None,
+    }, params.version, context).await;
+    if let Ok(user) = &res {
+        events.publish(UserEvent::updated(&old, user, context));
+    }
+    match res {
+        Ok(DbUser {
+               id,
+               first_name,
+               last_name,
+               birthday_date,
+               city,
+               version,
+               created_at,
+               updated_at,
+               ..
+           }) => Ok(Response {
+            id,
+            first_name,
+            last_name,
+            birthday_date,
+            city,
+            version,
+            created_at,
+            updated_at,
+        }),
+        Err(UpdateError::NotFound) => Err(Error::NotFound),
+        Err(UpdateError::VersionMismatch) => Err(Error::VersionMismatch),
+        Err(UpdateError::Unknown) => Err(Error::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::events::{EventBus, RecordingSubscriber};
+    use crate::repository::history::ChangeContext;
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::domain::entities::{BirthdayDate, BirthdayRules, CityName, DateFormat, FieldError, FirstName, LastName, TextRules, UserId, ValidationErrors};
+    use crate::domain::rules::UserRules;
+    use crate::domain::patch_user::{Error, execute, execute_json_patch, Operation, OperationError, Params, Request};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::{DbUser, Repository};
+
+    fn insert_user_values() -> DbUser {
+        DbUser {
+            id: Uuid::from(UserId::id()),
+            first_name: String::from(FirstName::name()),
+            last_name: String::from(LastName::name()),
+            birthday_date: BirthdayDate::date_native(),
+            city: String::from(CityName::name()),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+
+    async fn insert_user(repo: &InMemoryRepository) -> DbUser {
+        repo.insert(insert_user_values(), &ChangeContext::test()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn patch_domain_works() {
+        let repo = InMemoryRepository::new();
+        let db_user = insert_user(&repo).await;
+        let request = Request { city: Some(String::from("paris")), ..Request::default() };
+        let res = execute(&repo, &EventBus::new(), &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, request).await.unwrap();
+        assert_eq!(res.city, "Paris");
+        assert_eq!(res.first_name, db_user.first_name);
+        assert_eq!(res.last_name, db_user.last_name);
+        assert_eq!(res.birthday_date, db_user.birthday_date);
+        assert_eq!(repo.get(UserId::from(db_user.id)).await.unwrap().city, "Paris");
+    }
+
+    #[tokio::test]
+    async fn patch_domain_fail_version_mismatch() {
+        let repo = InMemoryRepository::new();
+        let db_user = insert_user(&repo).await;
+        let request = Request { city: Some(String::from("paris")), ..Request::default() };
+        let res = execute(&repo, &EventBus::new(), &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version + 1 }, request).await;
+        assert_eq!(res.err().unwrap(), Error::VersionMismatch);
+    }
+
+    #[tokio::test]
+    async fn patch_domain_fail_stale_version_before_merging() {
+        let repo = InMemoryRepository::new();
+        let db_user = insert_user(&repo).await;
+        let events = RecordingSubscriber::new();
+        // another writer changed the user since the client read version 1
+        repo.update(UserId::from(db_user.id), DbUser { last_name: String::from("Other"), ..db_user.clone() }, db_user.version, &ChangeContext::test()).await.unwrap();
+        let request = Request { city: Some(String::from("paris")), ..Request::default() };
+        let res = execute(&repo, &events, &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, request).await;
+        assert_eq!(res.err().unwrap(), Error::VersionMismatch);
+        let operations = vec![Operation::Test { path: String::from("/last_name"), value: String::from("Other") }];
+        let res = execute_json_patch(&repo, &events, &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, operations, None).await;
+        assert_eq!(res.err().unwrap(), Error::VersionMismatch);
+        assert_eq!(repo.get(UserId::from(db_user.id)).await.unwrap().city, db_user.city);
+        assert!(events.events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn patch_domain_fail_bad_request() {
+        let repo = InMemoryRepository::new();
+        let db_user = insert_user(&repo).await;
+        let request = Request { first_name: Some(String::from(FirstName::bad())), ..Request::default() };
+        let res = execute(&repo, &EventBus::new(), &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, request).await;
+        assert_eq!(res.err().unwrap(), Error::BadRequest(ValidationErrors(vec![FieldError { field: "first_name", code: "empty" }])));
+        assert_eq!(repo.get(UserId::from(db_user.id)).await.unwrap().first_name, db_user.first_name);
+    }
+
+    #[tokio::test]
+    async fn patch_domain_fail_not_found() {
+        let repo = InMemoryRepository::new();
+        let request = Request { city: Some(String::from("paris")), ..Request::default() };
+        let res = execute(&repo, &EventBus::new(), &UserRules::default(), &ChangeContext::test(), Params { id: UserId::id().to_string(), version: 1 }, request).await;
+        assert_eq!(res.err().unwrap(), Error::NotFound);
+    }
+
+    #[tokio::test]
+    async fn json_patch_domain_works() {
+        let repo = InMemoryRepository::new();
+        let db_user = insert_user(&repo).await;
+        let operations = vec![
+            Operation::Test { path: String::from("/city"), value: db_user.city.clone() },
+            Operation::Replace { path: String::from("/city"), value: String::from("paris") },
+            Operation::Remove { path: String::from("/first_name") },
+            Operation::Add { path: String::from("/first_name"), value: String::from("hugo") },
+        ];
+        let res = execute_json_patch(&repo, &EventBus::new(), &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, operations, None).await.unwrap();
+        assert_eq!(res.city, "Paris");
+        assert_eq!(res.first_name, "hugo");
+        assert_eq!(res.last_name, db_user.last_name);
+    }
+
+    #[tokio::test]
+    async fn json_patch_domain_fail_test_is_atomic() {
+        let repo = InMemoryRepository::new();
+        let db_user = insert_user(&repo).await;
+        let operations = vec![
+            Operation::Replace { path: String::from("/city"), value: String::from("paris") },
+            Operation::Test { path: String::from("/last_name"), value: String::from("someone else") },
+        ];
+        let res = execute_json_patch(&repo, &EventBus::new(), &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, operations, None).await;
+        assert_eq!(res.err().unwrap(), Error::TestFailed(OperationError { index: Some(1), path: String::from("/last_name"), reason: "test failed" }));
+        assert_eq!(repo.get(UserId::from(db_user.id)).await.unwrap().city, db_user.city);
+    }
+
+    #[tokio::test]
+    async fn json_patch_domain_fail_unprocessable() {
+        let repo = InMemoryRepository::new();
+        let db_user = insert_user(&repo).await;
+        let removed = vec![Operation::Remove { path: String::from("/city") }];
+        let invalid = vec![Operation::Replace { path: String::from("/birthday_date"), value: String::from("03-10-1994") }];
+        let read_only = vec![Operation::Replace { path: String::from("/id"), value: UserId::id().to_string() }];
+        let res = execute_json_patch(&repo, &EventBus::new(), &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, removed, None).await;
+        assert_eq!(res.err().unwrap(), Error::Unprocessable(OperationError { index: None, path: String::from("/city"), reason: "field is required" }));
+        let res = execute_json_patch(&repo, &EventBus::new(), &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, invalid, None).await;
+        assert_eq!(res.err().unwrap(), Error::Unprocessable(OperationError { index: None, path: String::from("/birthday_date"), reason: "invalid_format" }));
+        let res = execute_json_patch(&repo, &EventBus::new(), &UserRules::default(), &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, read_only, None).await;
+        assert_eq!(res.err().unwrap(), Error::Unprocessable(OperationError { index: Some(0), path: String::from("/id"), reason: "id is read only" }));
+    }
+
+    #[tokio::test]
+    async fn json_patch_domain_keeps_untouched_fields_under_new_rules() {
+        let repo = InMemoryRepository::new();
+        let db_user = repo.insert(DbUser { first_name: String::from("Maximilien"), ..insert_user_values() }, &ChangeContext::test()).await.unwrap();
+        // written before the rules got stricter: long name, too young, iso dates no longer read
+        let rules = UserRules {
+            text: TextRules { name_max_length: 5, ..TextRules::default() },
+            birthday: BirthdayRules { min_age: 150, formats: vec![DateFormat::Numeric], ..BirthdayRules::default() },
+            ..UserRules::default()
+        };
+        let operations = vec![
+            Operation::Test { path: String::from("/birthday_date"), value: db_user.birthday_date.format("%Y-%m-%d").to_string() },
+            Operation::Replace { path: String::from("/city"), value: String::from("lyon") },
+        ];
+        let res = execute_json_patch(&repo, &EventBus::new(), &rules, &ChangeContext::test(), Params { id: db_user.id.to_string(), version: db_user.version }, operations, None).await.unwrap();
+        assert_eq!(res.city, "Lyon");
+        assert_eq!(res.first_name, db_user.first_name);
+        assert_eq!(res.birthday_date, db_user.birthday_date);
+
+        // a changed field must meet them
+        let operations = vec![Operation::Replace { path: String::from("/first_name"), value: db_user.first_name.clone() }];
+        let res = execute_json_patch(&repo, &EventBus::new(), &rules, &ChangeContext::test(), Params { id: db_user.id.to_string(), version: res.version }, operations, None).await;
+        assert_eq!(res.err().unwrap(), Error::Unprocessable(OperationError { index: None, path: String::from("/first_name"), reason: "too_long" }));
+    }
+}
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Duration, Utc};
+use crate::domain::clock::Clock;
+use crate::repository::history::ChangeContext;
+use crate::repository::user::{PurgeError, Repository};
+
+#[derive(Debug)]
+pub struct Request {
+    /// Deleted users are kept this many whole days before being purged.
+    pub retention_days: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub purged: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Unknown,
+}
+
+pub async fn execute(repo: &dyn Repository, clock: &dyn Clock, context: &ChangeContext, req: Request) -> Result<Response, Error> {
+    let cutoff = (clock.today() - Duration::days(req.retention_days as i64)).and_hms_opt(0, 0, 0).unwrap();
+    match repo.purge(DateTime::<Utc>::from_naive_utc_and_offset(cutoff, Utc), context).await {
+        Ok(purged) => Ok(Response { purged }),
+        Err(PurgeError::Unknown) => Err(Error::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::history::ChangeContext;
+    use chrono::{Duration, NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::domain::clock::FixedClock;
+    use crate::domain::entities::UserId;
+    use crate::domain::purge_users::{execute, Request, Response};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::{DbUser, Repository};
+
+    #[tokio::test]
+    async fn purge_domain_keeps_retention() {
+        let repo = InMemoryRepository::new();
+        let db_user = repo.insert(DbUser {
+            id: Uuid::new_v4(),
+            first_name: "Hugo".to_string(),
+            last_name: "Muf".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(1994, 10, 3).unwrap(),
+            city: "Nice".to_string(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap();
+        repo.delete(UserId::from(db_user.id), 1, &ChangeContext::test()).await.unwrap();
+        let today = Utc::now().date_naive();
+        let res = execute(&repo, &FixedClock(today + Duration::days(30)), &ChangeContext::test(), Request { retention_days: 30 }).await;
+        assert_eq!(res, Ok(Response { purged: 0 }));
+        let res = execute(&repo, &FixedClock(today + Duration::days(31)), &ChangeContext::test(), Request { retention_days: 30 }).await;
+        assert_eq!(res, Ok(Response { purged: 1 }));
+    }
+}
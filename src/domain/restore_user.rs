@@ -0,0 +1,108 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+use crate::domain::entities::{UserId, ValidationErrors};
+use crate::domain::events::{EventPublisher, UserEvent};
+use crate::repository::user::{DbUser, Repository, RestoreError};
+use crate::repository::history::ChangeContext;
+
+#[derive(Debug)]
+pub struct Request {
+    pub id: String,
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub birthday_date: NaiveDate,
+    pub city: String,
+    pub version: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    BadRequest(ValidationErrors),
+    NotFound,
+    NotDeleted,
+    Unknown,
+}
+
+pub async fn execute(repo: &dyn Repository, events: &dyn EventPublisher, context: &ChangeContext, req: Request) -> Result<Response, Error> {
+    let id = UserId::try_from(req.id).map_err(|error| Error::BadRequest(ValidationErrors::single("id", error)))?;
+    let res = repo.restore(id, context).await;
+    if let Ok(user) = &res {
+        events.publish(UserEvent::restored(user, context));
+    }
+    match res {
+        Ok(DbUser {
+               id,
+               first_name,
+               last_name,
+               birthday_date,
+               city,
+               version,
+               created_at,
+               updated_at,
+               ..
+           }) => Ok(Response {
+            id,
+            first_name,
+            last_name,
+            birthday_date,
+            city,
+            version,
+            created_at,
+            updated_at,
+        }),
+        Err(RestoreError::NotFound) => Err(Error::NotFound),
+        Err(RestoreError::NotDeleted) => Err(Error::NotDeleted),
+        Err(RestoreError::Unknown) => Err(Error::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::events::EventBus;
+    use crate::repository::history::ChangeContext;
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::domain::entities::{BirthdayDate, CityName, FirstName, LastName, UserId, UserIdError, ValidationErrors};
+    use crate::domain::restore_user::{execute, Error, Request};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::{DbUser, Repository};
+
+    #[tokio::test]
+    async fn restore_domain_works() {
+        let repo = InMemoryRepository::new();
+        let db_user = repo.insert(DbUser {
+            id: Uuid::from(UserId::id()),
+            first_name: String::from(FirstName::name()),
+            last_name: String::from(LastName::name()),
+            birthday_date: BirthdayDate::date_native(),
+            city: String::from(CityName::name()),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap();
+        let res = execute(&repo, &EventBus::new(), &ChangeContext::test(), Request { id: db_user.id.to_string() }).await;
+        assert_eq!(res.err().unwrap(), Error::NotDeleted);
+        repo.delete(UserId::from(db_user.id), db_user.version, &ChangeContext::test()).await.unwrap();
+        let res = execute(&repo, &EventBus::new(), &ChangeContext::test(), Request { id: db_user.id.to_string() }).await.unwrap();
+        assert_eq!(res.id, db_user.id);
+        assert_eq!(res.version, 3);
+        assert_eq!(res.created_at, db_user.created_at);
+    }
+
+    #[tokio::test]
+    async fn restore_domain_fail() {
+        let repo = InMemoryRepository::new();
+        let res = execute(&repo, &EventBus::new(), &ChangeContext::test(), Request { id: UserId::id().to_string() }).await;
+        assert_eq!(res.err().unwrap(), Error::NotFound);
+        let res = execute(&repo, &EventBus::new(), &ChangeContext::test(), Request { id: "".to_string() }).await;
+        assert_eq!(res.err().unwrap(), Error::BadRequest(ValidationErrors::single("id", UserIdError::Malformed)));
+    }
+}
@@ -0,0 +1,23 @@
+use std::sync::Arc;
+use crate::domain::clock::{Clock, SystemClock};
+use crate::domain::entities::{BirthdayRules, TextRules};
+use crate::domain::id_generator::{IdGenerator, UuidV4Generator};
+
+/// What the write use cases check users against and stamp them with, built once by `main`.
+pub struct UserRules {
+    pub text: TextRules,
+    pub birthday: BirthdayRules,
+    pub clock: Arc<dyn Clock>,
+    pub ids: Arc<dyn IdGenerator>,
+}
+
+impl Default for UserRules {
+    fn default() -> Self {
+        UserRules {
+            text: TextRules::default(),
+            birthday: BirthdayRules::default(),
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(UuidV4Generator),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::domain::entities::{UserId, ValidationCode, ValidationErrors};
+use crate::repository::history::{DbChange, DbChangePage, HistoryQuery};
+use crate::repository::user::{FetchAllError, FetchOneError, Repository};
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Default)]
+pub struct Request {
+    pub id: String,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct Page {
+    pub changes: Vec<DbChange>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub next_offset: Option<i64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    BadRequest(ValidationErrors),
+    NotFound,
+    Unknown,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PageError {
+    OutOfRange,
+}
+
+impl ValidationCode for PageError {
+    fn code(&self) -> &'static str {
+        match self {
+            PageError::OutOfRange => "out_of_range",
+        }
+    }
+}
+
+pub(crate) fn check_range(value: i64, min: i64, max: i64) -> Result<i64, PageError> {
+    if value < min || value > max {
+        return Err(PageError::OutOfRange);
+    }
+    Ok(value)
+}
+
+pub async fn execute(repo: &dyn Repository, req: Request) -> Result<Page, Error> {
+    let mut errors = ValidationErrors::new();
+    let (id, limit, offset) = match (
+        errors.check("id", UserId::try_from(req.id)),
+        errors.check("limit", check_range(req.limit.unwrap_or(DEFAULT_LIMIT), 1, MAX_LIMIT)),
+        errors.check("offset", check_range(req.offset.unwrap_or(0), 0, i64::MAX)),
+    ) {
+        (Some(id), Some(limit), Some(offset)) => (id, limit, offset),
+        _ => return Err(Error::BadRequest(errors)),
+    };
+    match repo.history(id, HistoryQuery { limit, offset }).await {
+        // users created before the history was recorded have none yet
+        Ok(DbChangePage { total: 0, .. }) => match repo.get(id).await {
+            Ok(_) => Ok(Page { changes: vec![], total: 0, limit, offset, next_offset: None }),
+            Err(FetchOneError::NotFound) => Err(Error::NotFound),
+            Err(FetchOneError::Unknown) => Err(Error::Unknown),
+        },
+        Ok(DbChangePage { changes, total }) => {
+            let next_offset = if offset + limit < total { Some(offset + limit) } else { None };
+            Ok(Page { changes, total, limit, offset, next_offset })
+        }
+        Err(FetchAllError::Unknown) => Err(Error::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::domain::entities::{BirthdayDate, CityName, FieldError, FirstName, LastName, UserId, ValidationErrors};
+    use crate::domain::user_history::{execute, Error, Request};
+    use crate::repository::history::{ChangeContext, Operation};
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::{DbUser, Repository};
+
+    #[tokio::test]
+    async fn user_history_domain_works() {
+        let repo = InMemoryRepository::new();
+        let db_user = repo.insert(DbUser {
+            id: Uuid::from(UserId::id()),
+            first_name: String::from(FirstName::name()),
+            last_name: String::from(LastName::name()),
+            birthday_date: BirthdayDate::date_native(),
+            city: String::from(CityName::name()),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap();
+        let support = ChangeContext { actor: "support".to_string(), request_id: "req-1".to_string() };
+        let moved = DbUser { city: "Paris".to_string(), ..db_user.clone() };
+        repo.update(UserId::from(db_user.id), moved, 1, &support).await.unwrap();
+        repo.delete(UserId::from(db_user.id), 2, &support).await.unwrap();
+
+        let res = execute(&repo, Request { id: db_user.id.to_string(), limit: Some(2), ..Request::default() }).await.unwrap();
+        assert_eq!(res.total, 3);
+        assert_eq!(res.next_offset, Some(2));
+        let operations: Vec<Operation> = res.changes.iter().map(|change| change.operation).collect();
+        assert_eq!(operations, vec![Operation::Insert, Operation::Update]);
+        let update = &res.changes[1];
+        assert_eq!(update.old.as_ref().unwrap().city, String::from(CityName::name()));
+        assert_eq!(update.new.as_ref().unwrap().city, "Paris");
+        assert_eq!(update.actor, "support");
+        assert_eq!(update.request_id, "req-1");
+
+        // deleted users keep their history
+        let res = execute(&repo, Request { id: db_user.id.to_string(), offset: Some(2), ..Request::default() }).await.unwrap();
+        assert_eq!(res.changes[0].operation, Operation::Delete);
+        assert_eq!(res.next_offset, None);
+    }
+
+    #[tokio::test]
+    async fn user_history_domain_fail() {
+        let repo = InMemoryRepository::new();
+        let res = execute(&repo, Request { id: UserId::id().to_string(), ..Request::default() }).await;
+        assert_eq!(res.err().unwrap(), Error::NotFound);
+        let res = execute(&repo, Request { id: UserId::id().to_string(), limit: Some(0), offset: Some(-1) }).await;
+        assert_eq!(res.err().unwrap(), Error::BadRequest(ValidationErrors(vec![
+            FieldError { field: "limit", code: "out_of_range" },
+            FieldError { field: "offset", code: "out_of_range" },
+        ])));
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::Arc;
+use std::time::Duration;
+use sqlx::postgres::PgListener;
+use crate::domain::events::EventPublisher;
+use crate::repository::change_feed::{ChangeNotification, CHANNEL};
+use crate::repository::user::PostgresRepository;
+
+/// Publishes the changes made by the other replicas to the subscribers of this one, so local
+/// caches and streams hear about every change whichever instance made it. Notifications sent
+/// while the connection is down are lost, so subscribers are told to resync once it is back;
+/// the outbox remains the reliable feed.
+pub struct ChangeListener {
+    repo: Arc<PostgresRepository>,
+    events: Arc<dyn EventPublisher>,
+    /// Whether a connection listened before, the next one follows a gap.
+    listened: bool,
+    /// Wait before connecting again after the database refused to.
+    pub retry_delay: Duration,
+}
+
+impl ChangeListener {
+    pub fn new(repo: Arc<PostgresRepository>, events: Arc<dyn EventPublisher>) -> Self {
+        ChangeListener { repo, events, listened: false, retry_delay: Duration::from_secs(5) }
+    }
+
+    /// Called once a connection listens, changes made since the previous one dropped were not heard.
+    fn listening(&mut self) {
+        if self.listened {
+            self.events.publish_resync();
+        }
+        self.listened = true;
+    }
+
+    async fn listen(&self) -> Result<PgListener, sqlx::Error> {
+        let mut listener = PgListener::connect_with(self.repo.db_pool.as_ref().unwrap()).await?;
+        listener.listen(CHANNEL).await?;
+        Ok(listener)
+    }
+
+    /// Publishes the event or purge of a notification, unless this instance made the change and already did.
+    pub async fn dispatch(&self, payload: &str) {
+        let notification: ChangeNotification = match serde_json::from_str(payload) {
+            Ok(notification) => notification,
+            Err(_) => return tracing::warn!("unreadable {} notification: {}", CHANNEL, payload),
+        };
+        if notification.origin == self.repo.instance_id {
+            return;
+        }
+        if notification.purge {
+            return self.events.publish_purge();
+        }
+        let event = match (notification.event, notification.message_id) {
+            (Some(event), _) => event,
+            (None, Some(message_id)) => match self.repo.outbox_event(message_id).await {
+                Ok(Some(event)) => event,
+                Ok(None) => return tracing::warn!(message_id, "notified change missing from the outbox"),
+                Err(_) => return tracing::warn!(message_id, "notified change could not be read"),
+            },
+            (None, None) => return tracing::warn!("notified change without event nor message: {}", payload),
+        };
+        self.events.publish(event);
+    }
+
+    pub async fn run(mut self) {
+        loop {
+            match self.listen().await {
+                Ok(mut listener) => {
+                    self.listening();
+                    // `try_recv` would reconnect by itself, without telling: connect again here instead
+                    while let Ok(Some(notification)) = listener.try_recv().await {
+                        self.dispatch(notification.payload()).await;
+                    }
+                    tracing::warn!("change feed connection lost, subscribers resync once it is back");
+                }
+                Err(_) => {
+                    tracing::warn!("change feed unavailable");
+                    tokio::time::sleep(self.retry_delay).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use chrono::{NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::domain::events::{EventPublisher, RecordingSubscriber, UserEvent};
+    use crate::relay::change_feed::ChangeListener;
+    use crate::repository::change_feed::ChangeNotification;
+    use crate::repository::history::ChangeContext;
+    use crate::repository::user::{DbUser, PostgresRepository};
+
+    fn event() -> UserEvent {
+        UserEvent::created(&DbUser {
+            id: Uuid::new_v4(),
+            first_name: "Hugo".to_string(),
+            last_name: "Muf".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            city: "Nice".to_string(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test())
+    }
+
+    #[tokio::test]
+    async fn dispatch_publishes_changes_of_other_instances() {
+        let repo = Arc::new(PostgresRepository { db_pool: None, instance_id: Uuid::new_v4() });
+        let events = Arc::new(RecordingSubscriber::new());
+        let listener = ChangeListener::new(repo.clone(), events.clone() as Arc<dyn EventPublisher>);
+        let own = ChangeNotification { origin: repo.instance_id, message_id: Some(1), event: Some(event()), purge: false };
+        listener.dispatch(&serde_json::to_string(&own).unwrap()).await;
+        listener.dispatch("not json").await;
+        assert!(events.events().is_empty());
+
+        let other = ChangeNotification { origin: Uuid::new_v4(), message_id: Some(2), event: Some(event()), purge: false };
+        listener.dispatch(&serde_json::to_string(&other).unwrap()).await;
+        assert_eq!(events.events(), vec![other.event.unwrap()]);
+        assert_eq!(*events.purges.read().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn dispatch_publishes_purges_of_other_instances() {
+        let repo = Arc::new(PostgresRepository { db_pool: None, instance_id: Uuid::new_v4() });
+        let events = Arc::new(RecordingSubscriber::new());
+        let listener = ChangeListener::new(repo.clone(), events.clone() as Arc<dyn EventPublisher>);
+        let own = ChangeNotification { origin: repo.instance_id, message_id: None, event: None, purge: true };
+        listener.dispatch(&serde_json::to_string(&own).unwrap()).await;
+        assert_eq!(*events.purges.read().unwrap(), 0);
+
+        let payload = format!(r#"{{"origin":"{}","purge":true}}"#, Uuid::new_v4());
+        listener.dispatch(&payload).await;
+        assert_eq!(*events.purges.read().unwrap(), 1);
+        assert!(events.events().is_empty());
+    }
+
+    #[test]
+    fn listening_again_resyncs_subscribers() {
+        let repo = Arc::new(PostgresRepository { db_pool: None, instance_id: Uuid::new_v4() });
+        let events = Arc::new(RecordingSubscriber::new());
+        let mut listener = ChangeListener::new(repo, events.clone() as Arc<dyn EventPublisher>);
+        listener.listening();
+        assert_eq!(*events.resyncs.read().unwrap(), 0);
+        listener.listening();
+        listener.listening();
+        assert_eq!(*events.resyncs.read().unwrap(), 2);
+    }
+}
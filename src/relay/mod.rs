@@ -0,0 +1,175 @@
+use std::cmp::min;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use crate::relay::sink::Sink;
+use crate::repository::outbox::{Outbox, OutboxError};
+
+pub mod sink;
+pub mod change_feed;
+pub mod webhook;
+
+/// Drains the outbox to a sink. A message is marked published only once the sink accepted it,
+/// so a crash in between sends it again: delivery is at least once and consumers deduplicate
+/// on the message id.
+pub struct Relay {
+    outbox: Arc<dyn Outbox>,
+    sink: Arc<dyn Sink>,
+    pub batch_size: i64,
+    pub poll_interval: Duration,
+    /// How long a claimed message stays hidden from other relays.
+    pub lease: Duration,
+    /// Wait before the first retry, doubled on each failure up to `max_retry_delay`.
+    pub retry_delay: Duration,
+    pub max_retry_delay: Duration,
+}
+
+impl Relay {
+    pub fn new(outbox: Arc<dyn Outbox>, sink: Arc<dyn Sink>) -> Self {
+        Relay {
+            outbox,
+            sink,
+            batch_size: 50,
+            poll_interval: Duration::from_secs(1),
+            lease: Duration::from_secs(60),
+            retry_delay: Duration::from_secs(1),
+            max_retry_delay: Duration::from_secs(300),
+        }
+    }
+
+    /// Sends the messages due now, returning how many were published.
+    pub async fn run_once(&self) -> Result<usize, OutboxError> {
+        let messages = self.outbox.claim(self.batch_size, self.lease).await?;
+        let mut published = 0;
+        for message in messages {
+            match self.sink.send(&message).await {
+                Ok(()) => {
+                    self.outbox.mark_published(message.id).await?;
+                    published += 1;
+                }
+                Err(error) => {
+                    tracing::warn!(id = message.id, attempts = message.attempts + 1, "outbox delivery failed: {}", error.0);
+                    let retry_at = Utc::now() + chrono::Duration::from_std(self.backoff(message.attempts)).unwrap_or_else(|_| chrono::Duration::zero());
+                    self.outbox.mark_failed(message.id, &error.0, retry_at).await?;
+                }
+            }
+        }
+        Ok(published)
+    }
+
+    /// Runs until the process stops, waiting `poll_interval` whenever the outbox is empty.
+    pub async fn run(self) {
+        loop {
+            match self.run_once().await {
+                Ok(published) if published as i64 == self.batch_size => continue,
+                Ok(_) => {}
+                Err(_) => tracing::warn!("outbox unavailable"),
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    fn backoff(&self, attempts: i32) -> Duration {
+        backoff(self.retry_delay, self.max_retry_delay, attempts)
+    }
+}
+
+/// `retry_delay` doubled for each attempt already made, at most `max_retry_delay`.
+pub(crate) fn backoff(retry_delay: Duration, max_retry_delay: Duration, attempts: i32) -> Duration {
+    let factor = 2u32.saturating_pow(min(attempts.max(0) as u32, 16));
+    min(retry_delay.saturating_mul(factor), max_retry_delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use async_trait::async_trait;
+    use chrono::{NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::relay::Relay;
+    use crate::relay::sink::{Sink, SinkError};
+    use crate::repository::history::ChangeContext;
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::outbox::{Outbox, OutboxMessage};
+    use crate::repository::user::{DbUser, Repository};
+
+    /// Fails the first `failures` sends, then keeps the messages.
+    struct FlakySink {
+        failures: Mutex<usize>,
+        sent: Mutex<Vec<OutboxMessage>>,
+    }
+
+    #[async_trait]
+    impl Sink for FlakySink {
+        async fn send(&self, message: &OutboxMessage) -> Result<(), SinkError> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(SinkError("unavailable".to_string()));
+            }
+            self.sent.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    async fn insert_user(repo: &InMemoryRepository) -> DbUser {
+        repo.insert(DbUser {
+            id: Uuid::new_v4(),
+            first_name: "Hugo".to_string(),
+            last_name: "Muf".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            city: "Nice".to_string(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn relay_publishes_each_message_once() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let user = insert_user(&repo).await;
+        let sink = Arc::new(FlakySink { failures: Mutex::new(0), sent: Mutex::new(vec![]) });
+        let relay = Relay::new(repo.clone() as Arc<dyn Outbox>, sink.clone() as Arc<dyn Sink>);
+        assert_eq!(relay.run_once().await.unwrap(), 1);
+        assert_eq!(relay.run_once().await.unwrap(), 0);
+        let sent = sink.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].user_id, user.id);
+    }
+
+    #[tokio::test]
+    async fn relay_retries_failed_messages() {
+        let repo = Arc::new(InMemoryRepository::new());
+        insert_user(&repo).await;
+        let sink = Arc::new(FlakySink { failures: Mutex::new(1), sent: Mutex::new(vec![]) });
+        let mut relay = Relay::new(repo.clone() as Arc<dyn Outbox>, sink.clone() as Arc<dyn Sink>);
+        relay.retry_delay = Duration::ZERO;
+        assert_eq!(relay.run_once().await.unwrap(), 0);
+        assert_eq!(relay.run_once().await.unwrap(), 1);
+        assert_eq!(sink.sent.lock().unwrap()[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn relay_waits_before_retrying() {
+        let repo = Arc::new(InMemoryRepository::new());
+        insert_user(&repo).await;
+        let sink = Arc::new(FlakySink { failures: Mutex::new(1), sent: Mutex::new(vec![]) });
+        let relay = Relay::new(repo.clone() as Arc<dyn Outbox>, sink.clone() as Arc<dyn Sink>);
+        assert_eq!(relay.run_once().await.unwrap(), 0);
+        assert_eq!(relay.run_once().await.unwrap(), 0);
+        assert!(sink.sent.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn relay_backoff_is_capped() {
+        let repo = Arc::new(InMemoryRepository::new());
+        let sink = Arc::new(FlakySink { failures: Mutex::new(0), sent: Mutex::new(vec![]) });
+        let relay = Relay::new(repo as Arc<dyn Outbox>, sink as Arc<dyn Sink>);
+        assert_eq!(relay.backoff(0), Duration::from_secs(1));
+        assert_eq!(relay.backoff(3), Duration::from_secs(8));
+        assert_eq!(relay.backoff(40), Duration::from_secs(300));
+    }
+}
@@ -0,0 +1,164 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast;
+use crate::repository::outbox::OutboxMessage;
+
+#[derive(Debug, PartialEq)]
+pub struct SinkError(pub String);
+
+/// Where the relay delivers outbox messages. `Ok` means the message is safely handed over.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    async fn send(&self, message: &OutboxMessage) -> Result<(), SinkError>;
+}
+
+/// Appends each message as one JSON line.
+pub struct LogFileSink {
+    path: PathBuf,
+}
+
+impl LogFileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        LogFileSink { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl Sink for LogFileSink {
+    async fn send(&self, message: &OutboxMessage) -> Result<(), SinkError> {
+        let mut line = serde_json::to_vec(message).map_err(|e| SinkError(e.to_string()))?;
+        line.push(b'\n');
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(&self.path).await
+            .map_err(|e| SinkError(e.to_string()))?;
+        file.write_all(&line).await.map_err(|e| SinkError(e.to_string()))?;
+        file.sync_data().await.map_err(|e| SinkError(e.to_string()))
+    }
+}
+
+/// POSTs each message as JSON, with its id in `Idempotency-Key`. Any status but 2xx is retried.
+pub struct HttpSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpSink {
+    pub fn new(url: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        HttpSink { client, url: url.to_string() }
+    }
+}
+
+#[async_trait]
+impl Sink for HttpSink {
+    async fn send(&self, message: &OutboxMessage) -> Result<(), SinkError> {
+        let body = serde_json::to_vec(message).map_err(|e| SinkError(e.to_string()))?;
+        let res = self.client.post(&self.url)
+            .header("Content-Type", "application/json")
+            .header("Idempotency-Key", message.id.to_string())
+            .body(body)
+            .send().await
+            .map_err(|e| SinkError(e.to_string()))?;
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(SinkError(format!("webhook answered {}", res.status())))
+        }
+    }
+}
+
+/// In-process stand-in for a message broker, consumers get their own receiver.
+/// A message nobody is subscribed to counts as not delivered.
+pub struct BrokerSink {
+    sender: broadcast::Sender<OutboxMessage>,
+}
+
+impl BrokerSink {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        BrokerSink { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OutboxMessage> {
+        self.sender.subscribe()
+    }
+}
+
+#[async_trait]
+impl Sink for BrokerSink {
+    async fn send(&self, message: &OutboxMessage) -> Result<(), SinkError> {
+        self.sender.send(message.clone())
+            .map(|_| ())
+            .map_err(|_| SinkError("no broker subscriber".to_string()))
+    }
+}
+
+/// Hands each message to every sink, delivered only once all of them accepted it.
+/// A retry sends it again to all of them, so each sink deduplicates on the message id.
+pub struct FanoutSink {
+    sinks: Vec<Arc<dyn Sink>>,
+}
+
+impl FanoutSink {
+    pub fn new(sinks: Vec<Arc<dyn Sink>>) -> Self {
+        FanoutSink { sinks }
+    }
+}
+
+#[async_trait]
+impl Sink for FanoutSink {
+    async fn send(&self, message: &OutboxMessage) -> Result<(), SinkError> {
+        for sink in &self.sinks {
+            sink.send(message).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::domain::events::UserEvent;
+    use crate::relay::sink::{BrokerSink, LogFileSink, Sink};
+    use crate::repository::history::ChangeContext;
+    use crate::repository::outbox::OutboxMessage;
+
+    fn message(id: i64) -> OutboxMessage {
+        let user_id = Uuid::new_v4();
+        OutboxMessage {
+            id,
+            user_id,
+            event: UserEvent::deleted(user_id, "Nice", 2, Utc::now(), &ChangeContext::test()),
+            attempts: 0,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn log_file_sink_appends_json_lines() {
+        let path = std::env::temp_dir().join(format!("outbox-{}.log", Uuid::new_v4()));
+        let sink = LogFileSink::new(&path);
+        sink.send(&message(1)).await.unwrap();
+        sink.send(&message(2)).await.unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<OutboxMessage> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.iter().map(|message| message.id).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn broker_sink_needs_a_subscriber() {
+        let sink = BrokerSink::new(16);
+        assert!(sink.send(&message(1)).await.is_err());
+        let mut receiver = sink.subscribe();
+        let sent = message(2);
+        sink.send(&sent).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), sent);
+    }
+}
@@ -0,0 +1,373 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use url::{Host, Url};
+use crate::domain::entities::is_public_address;
+use crate::relay::backoff;
+use crate::relay::sink::{Sink, SinkError};
+use crate::repository::outbox::OutboxMessage;
+use crate::repository::webhook::{PendingDelivery, WebhookError, WebhookRepository};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const ID_HEADER: &str = "X-Webhook-Id";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}` keyed with the subscription secret.
+/// The timestamp is signed too, so receivers can refuse old replayed requests.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    let signature = mac.finalize().into_bytes();
+    format!("sha256={}", signature.iter().map(|byte| format!("{:02x}", byte)).collect::<String>())
+}
+
+/// Outbox sink fanning each message out to the webhook subscriptions listening to it.
+pub struct WebhookSink {
+    store: Arc<dyn WebhookRepository>,
+}
+
+impl WebhookSink {
+    pub fn new(store: Arc<dyn WebhookRepository>) -> Self {
+        WebhookSink { store }
+    }
+}
+
+#[async_trait]
+impl Sink for WebhookSink {
+    async fn send(&self, message: &OutboxMessage) -> Result<(), SinkError> {
+        let payload = json!({
+            "id": message.id,
+            "created_at": message.created_at,
+            "event": message.event,
+        });
+        self.store.queue_deliveries(message.id, message.event.event_type(), &payload).await
+            .map_err(|_| SinkError("webhook deliveries could not be queued".to_string()))
+    }
+}
+
+/// POSTs the queued deliveries, retrying failures with an exponential backoff and disabling
+/// a subscription after `max_failures` failed attempts in a row.
+pub struct WebhookDispatcher {
+    store: Arc<dyn WebhookRepository>,
+    pub timeout: Duration,
+    pub batch_size: i64,
+    pub poll_interval: Duration,
+    pub lease: Duration,
+    pub retry_delay: Duration,
+    pub max_retry_delay: Duration,
+    pub max_failures: i32,
+    /// Plain `http` receivers are refused unless set, as when subscribing.
+    pub allow_http: bool,
+    /// Lets deliveries reach private addresses, only meant for tests against a local receiver.
+    pub allow_private: bool,
+}
+
+impl WebhookDispatcher {
+    pub fn new(store: Arc<dyn WebhookRepository>) -> Self {
+        WebhookDispatcher {
+            store,
+            timeout: Duration::from_secs(10),
+            batch_size: 50,
+            poll_interval: Duration::from_secs(1),
+            lease: Duration::from_secs(60),
+            retry_delay: Duration::from_secs(5),
+            max_retry_delay: Duration::from_secs(3600),
+            max_failures: 10,
+            allow_http: false,
+            allow_private: false,
+        }
+    }
+
+    /// Resolves the receiver and returns a client pinned to the addresses checked, so a DNS answer
+    /// changing between the check and the connection cannot send the request inside the network.
+    /// Redirects are not followed, they could lead there too.
+    async fn client_for(&self, url: &str) -> Result<reqwest::Client, String> {
+        let url = Url::parse(url).map_err(|_| format!("{} is not a valid url", url))?;
+        match url.scheme() {
+            "https" => {}
+            "http" if self.allow_http => {}
+            scheme => return Err(format!("{} receivers are not allowed", scheme)),
+        }
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addrs: Vec<SocketAddr> = match url.host() {
+            Some(Host::Domain(domain)) => tokio::net::lookup_host((domain, port)).await
+                .map_err(|error| format!("{} could not be resolved: {}", domain, error))?
+                .collect(),
+            Some(Host::Ipv4(ip)) => vec![SocketAddr::new(ip.into(), port)],
+            Some(Host::Ipv6(ip)) => vec![SocketAddr::new(ip.into(), port)],
+            None => return Err(format!("{} has no host", url)),
+        };
+        if addrs.is_empty() {
+            return Err(format!("{} has no address", url));
+        }
+        if !self.allow_private && addrs.iter().any(|addr| !is_public_address(addr.ip())) {
+            return Err(format!("{} resolves to a private address", url.host_str().unwrap_or_default()));
+        }
+        let builder = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .redirect(reqwest::redirect::Policy::none());
+        let builder = match url.host() {
+            Some(Host::Domain(domain)) => builder.resolve_to_addrs(domain, &addrs),
+            _ => builder,
+        };
+        builder.build().map_err(|error| error.to_string())
+    }
+
+    /// Attempts the deliveries due now, returning how many succeeded.
+    pub async fn run_once(&self) -> Result<usize, WebhookError> {
+        let pending = self.store.claim_deliveries(self.batch_size, self.lease).await?;
+        let mut delivered = 0;
+        for PendingDelivery { delivery, url, secret } in pending {
+            let body = serde_json::to_vec(&delivery.payload).map_err(|_| WebhookError::Unknown)?;
+            let timestamp = Utc::now().timestamp();
+            let res = match self.client_for(&url).await {
+                Ok(client) => client.post(&url)
+                    .header("Content-Type", "application/json")
+                    .header(ID_HEADER, delivery.message_id.to_string())
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(SIGNATURE_HEADER, sign(&secret, timestamp, &body))
+                    .body(body)
+                    .send().await
+                    .map_err(|error| error.to_string()),
+                Err(error) => Err(error),
+            };
+            let (status, error) = match res {
+                Ok(res) if res.status().is_success() => {
+                    self.store.mark_delivered(delivery.id, res.status().as_u16() as i32).await?;
+                    delivered += 1;
+                    continue;
+                }
+                Ok(res) => (Some(res.status().as_u16() as i32), format!("answered {}", res.status())),
+                Err(error) => (None, error),
+            };
+            let delay = backoff(self.retry_delay, self.max_retry_delay, delivery.attempts);
+            let retry_at = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero());
+            if self.store.mark_delivery_failed(delivery.id, status, &error, retry_at, self.max_failures).await? {
+                tracing::warn!(subscription_id = %delivery.subscription_id, "webhook disabled after {} failures: {}", self.max_failures, error);
+            }
+        }
+        Ok(delivered)
+    }
+
+    pub async fn run(self) {
+        loop {
+            match self.run_once().await {
+                Ok(delivered) if delivered as i64 == self.batch_size => continue,
+                Ok(_) => {}
+                Err(_) => tracing::warn!("webhook deliveries unavailable"),
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::time::Duration;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use actix_web::http::header::LOCATION;
+    use actix_web::http::StatusCode;
+    use chrono::{NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::domain::events::UserEvent;
+    use crate::relay::Relay;
+    use crate::relay::sink::Sink;
+    use crate::relay::webhook::{sign, WebhookDispatcher, WebhookSink, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+    use crate::repository::history::ChangeContext;
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::outbox::Outbox;
+    use crate::repository::user::{DbUser, Repository};
+    use crate::repository::webhook::{DbWebhook, DeliveryStatus, WebhookRepository};
+
+    /// Event type, signature and body of every request a stand-in got.
+    type Received = Arc<Mutex<Vec<(String, String, Vec<u8>)>>>;
+
+    /// Local partner endpoint answering `status` and keeping the requests it got.
+    struct StandIn {
+        url: String,
+        status: Arc<AtomicU16>,
+        received: Received,
+    }
+
+    fn stand_in() -> StandIn {
+        let status = Arc::new(AtomicU16::new(200));
+        let received = Arc::new(Mutex::new(vec![]));
+        let (answer, kept) = (status.clone(), received.clone());
+        let server = HttpServer::new(move || {
+            let (answer, kept) = (answer.clone(), kept.clone());
+            App::new().route("/hooks", web::post().to(move |req: HttpRequest, body: web::Bytes| {
+                let (answer, kept) = (answer.clone(), kept.clone());
+                async move {
+                    let header = |name: &str| req.headers().get(name).unwrap().to_str().unwrap().to_string();
+                    kept.lock().unwrap().push((header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER), body.to_vec()));
+                    HttpResponse::build(StatusCode::from_u16(answer.load(Ordering::SeqCst)).unwrap()).finish()
+                }
+            }))
+                .route("/moved", web::post().to(|| async {
+                    HttpResponse::TemporaryRedirect().insert_header((LOCATION, "/hooks")).finish()
+                }))
+        })
+            .workers(1)
+            .bind(("127.0.0.1", 0)).unwrap();
+        let url = format!("http://{}/hooks", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        StandIn { url, status, received }
+    }
+
+    /// The stand-in listens on plain http on the loopback, refused outside tests.
+    fn local_dispatcher(repo: Arc<InMemoryRepository>) -> WebhookDispatcher {
+        let mut dispatcher = WebhookDispatcher::new(repo);
+        dispatcher.allow_http = true;
+        dispatcher.allow_private = true;
+        dispatcher
+    }
+
+    async fn subscribe(repo: &InMemoryRepository, url: &str, events: Vec<String>) -> DbWebhook {
+        repo.insert_webhook(DbWebhook {
+            id: Uuid::new_v4(),
+            url: url.to_string(),
+            events,
+            secret: String::from("whsec_test"),
+            active: true,
+            consecutive_failures: 0,
+            disabled_at: None,
+            created_at: Utc::now(),
+        }).await.unwrap()
+    }
+
+    async fn insert_user(repo: &InMemoryRepository) -> DbUser {
+        repo.insert(DbUser {
+            id: Uuid::new_v4(),
+            first_name: "Hugo".to_string(),
+            last_name: "Muf".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            city: "Nice".to_string(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }, &ChangeContext::test()).await.unwrap()
+    }
+
+    #[test]
+    fn sign_is_hmac_sha256_of_timestamp_and_body() {
+        // echo -n '1700000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(sign("secret", 1700000000, b"{}"), "sha256=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163");
+    }
+
+    #[actix_web::test]
+    async fn dispatcher_posts_signed_payloads() {
+        let partner = stand_in();
+        let repo = Arc::new(InMemoryRepository::new());
+        let webhook = subscribe(&repo, &partner.url, vec![String::from("UserCreated")]).await;
+        let user = insert_user(&repo).await;
+        let relay = Relay::new(repo.clone() as Arc<dyn Outbox>, Arc::new(WebhookSink::new(repo.clone())) as Arc<dyn Sink>);
+        relay.run_once().await.unwrap();
+        let dispatcher = local_dispatcher(repo.clone());
+        assert_eq!(dispatcher.run_once().await.unwrap(), 1);
+
+        let received = partner.received.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        let (timestamp, signature, body) = &received[0];
+        assert_eq!(signature, &sign(&webhook.secret, timestamp.parse().unwrap(), body));
+        let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(payload["event"]["type"], "UserCreated");
+        assert_eq!(payload["event"]["user_id"], user.id.to_string());
+        let log = repo.fetch_deliveries(webhook.id, 10, 0).await.unwrap();
+        assert_eq!(log.deliveries[0].status, DeliveryStatus::Delivered);
+        assert_eq!(log.deliveries[0].response_status, Some(200));
+    }
+
+    #[actix_web::test]
+    async fn dispatcher_skips_filtered_events() {
+        let partner = stand_in();
+        let repo = Arc::new(InMemoryRepository::new());
+        let webhook = subscribe(&repo, &partner.url, vec![String::from("UserDeleted")]).await;
+        insert_user(&repo).await;
+        let relay = Relay::new(repo.clone() as Arc<dyn Outbox>, Arc::new(WebhookSink::new(repo.clone())) as Arc<dyn Sink>);
+        relay.run_once().await.unwrap();
+        assert_eq!(local_dispatcher(repo.clone()).run_once().await.unwrap(), 0);
+        assert_eq!(repo.fetch_deliveries(webhook.id, 10, 0).await.unwrap().total, 0);
+    }
+
+    #[actix_web::test]
+    async fn dispatcher_retries_then_disables() {
+        let partner = stand_in();
+        partner.status.store(503, Ordering::SeqCst);
+        let repo = Arc::new(InMemoryRepository::new());
+        let webhook = subscribe(&repo, &partner.url, vec![]).await;
+        let sink = WebhookSink::new(repo.clone());
+        let event = UserEvent::deleted(Uuid::new_v4(), "Nice", 2, Utc::now(), &ChangeContext::test());
+        for id in 1..=2 {
+            sink.send(&crate::repository::outbox::OutboxMessage { id, user_id: event.user_id(), event: event.clone(), attempts: 0, created_at: Utc::now() }).await.unwrap();
+        }
+        let mut dispatcher = local_dispatcher(repo.clone());
+        dispatcher.retry_delay = Duration::ZERO;
+        dispatcher.batch_size = 1;
+        dispatcher.max_failures = 3;
+
+        assert_eq!(dispatcher.run_once().await.unwrap(), 0);
+        let log = repo.fetch_deliveries(webhook.id, 10, 0).await.unwrap();
+        let first = log.deliveries.iter().find(|delivery| delivery.message_id == 1).unwrap();
+        assert_eq!(first.attempts, 1);
+        assert_eq!(first.response_status, Some(503));
+        assert!(repo.get_webhook(webhook.id).await.unwrap().active);
+
+        // a success resets the count of failures in a row
+        partner.status.store(200, Ordering::SeqCst);
+        assert_eq!(dispatcher.run_once().await.unwrap(), 1);
+        assert_eq!(repo.get_webhook(webhook.id).await.unwrap().consecutive_failures, 0);
+
+        partner.status.store(500, Ordering::SeqCst);
+        for _ in 0..3 {
+            assert_eq!(dispatcher.run_once().await.unwrap(), 0);
+        }
+        let disabled = repo.get_webhook(webhook.id).await.unwrap();
+        assert!(!disabled.active);
+        assert!(disabled.disabled_at.is_some());
+        let log = repo.fetch_deliveries(webhook.id, 10, 0).await.unwrap();
+        assert_eq!(log.deliveries[0].status, DeliveryStatus::Cancelled);
+        assert_eq!(log.deliveries[0].attempts, 3);
+        // nothing is sent to a disabled subscription
+        assert_eq!(dispatcher.run_once().await.unwrap(), 0);
+        assert_eq!(partner.received.lock().unwrap().len(), 5);
+    }
+
+    #[actix_web::test]
+    async fn dispatcher_refuses_private_receivers_and_redirects() {
+        let partner = stand_in();
+        let repo = Arc::new(InMemoryRepository::new());
+        // stored before the checks existed, or pointing to a name resolving inside the network
+        let private = subscribe(&repo, &partner.url, vec![]).await;
+        insert_user(&repo).await;
+        let relay = Relay::new(repo.clone() as Arc<dyn Outbox>, Arc::new(WebhookSink::new(repo.clone())) as Arc<dyn Sink>);
+        relay.run_once().await.unwrap();
+        let mut dispatcher = WebhookDispatcher::new(repo.clone());
+        dispatcher.allow_http = true;
+        assert_eq!(dispatcher.run_once().await.unwrap(), 0);
+        let log = repo.fetch_deliveries(private.id, 10, 0).await.unwrap();
+        assert_eq!(log.deliveries[0].response_status, None);
+        assert!(log.deliveries[0].last_error.as_deref().unwrap().contains("private address"), "{:?}", log.deliveries[0].last_error);
+
+        let repo = Arc::new(InMemoryRepository::new());
+        let moved = subscribe(&repo, &partner.url.replace("/hooks", "/moved"), vec![]).await;
+        insert_user(&repo).await;
+        let relay = Relay::new(repo.clone() as Arc<dyn Outbox>, Arc::new(WebhookSink::new(repo.clone())) as Arc<dyn Sink>);
+        relay.run_once().await.unwrap();
+        assert_eq!(local_dispatcher(repo.clone()).run_once().await.unwrap(), 0);
+        let log = repo.fetch_deliveries(moved.id, 10, 0).await.unwrap();
+        assert_eq!(log.deliveries[0].response_status, Some(307));
+        assert!(partner.received.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::domain::entities::UserId;
+use crate::domain::events::{Subscriber, UserEvent};
+use crate::repository::history::{ChangeContext, DbChangePage, HistoryQuery};
+use crate::repository::user::{DbUser, DbUserPage, DeleteError, FetchAllError, FetchOneError, InsertError, PurgeError, Repository, RestoreError, UpdateError, UserQuery};
+
+/// Entries expiring after a fixed ttl, each with the sequence number it was inserted with.
+struct TtlMap<K, V> {
+    entries: HashMap<K, (V, Instant, u64)>,
+    /// Insertion order, with the sequence number telling whether the key was inserted again since.
+    order: VecDeque<(K, u64)>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> TtlMap<K, V> {
+    fn new() -> Self {
+        TtlMap { entries: HashMap::new(), order: VecDeque::new() }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        match self.entries.get(key) {
+            Some((value, expires_at, _)) if *expires_at > Instant::now() => Some(value.clone()),
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V, ttl: Duration, seq: u64) {
+        self.entries.insert(key.clone(), (value, Instant::now() + ttl, seq));
+        self.order.push_back((key, seq));
+        // keys removed or inserted again leave stale positions behind
+        if self.order.len() > 2 * self.entries.len() {
+            let entries = &self.entries;
+            self.order.retain(|(key, seq)| entries.get(key).is_some_and(|entry| entry.2 == *seq));
+        }
+    }
+
+    /// Sequence number of the oldest entry still kept.
+    fn oldest(&mut self) -> Option<u64> {
+        while let Some((key, seq)) = self.order.front() {
+            if self.entries.get(key).is_some_and(|entry| entry.2 == *seq) {
+                return Some(*seq);
+            }
+            self.order.pop_front();
+        }
+        None
+    }
+
+    fn evict_oldest(&mut self) {
+        if self.oldest().is_some() {
+            if let Some((key, _)) = self.order.pop_front() {
+                self.entries.remove(&key);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+struct Entries {
+    /// `None` remembers the user does not exist.
+    users: TtlMap<Uuid, Option<DbUser>>,
+    pages: TtlMap<UserQuery, DbUserPage>,
+    /// Shared by both maps, so the oldest entry of either can be told apart.
+    next_seq: u64,
+    /// Bumped by every invalidation, a read started before one must not store what it read.
+    generation: u64,
+}
+
+impl Entries {
+    fn next_seq(&mut self) -> u64 {
+        self.next_seq += 1;
+        self.next_seq
+    }
+
+    fn len(&self) -> usize {
+        self.users.entries.len() + self.pages.entries.len()
+    }
+
+    /// Evicts the oldest entries, users and pages alike, until at most `max_entries` are kept.
+    fn evict(&mut self, max_entries: usize) {
+        while self.len() > max_entries {
+            match (self.users.oldest(), self.pages.oldest()) {
+                (Some(user), Some(page)) if user < page => self.users.evict_oldest(),
+                (Some(_), None) => self.users.evict_oldest(),
+                (_, Some(_)) => self.pages.evict_oldest(),
+                (None, None) => break,
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Copy)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// Read-through cache in front of any repository, for `get` and `fetch_page`. Writes through it
+/// invalidate what they change, and as a `Subscriber` it also forgets the users changed by other
+/// instances, heard through the change feed. Past the ttl, an entry is read again anyway.
+/// `max_entries` bounds users and pages together, the oldest of either is evicted first.
+pub struct CachedRepository {
+    inner: Arc<dyn Repository>,
+    entries: Mutex<Entries>,
+    ttl: Duration,
+    max_entries: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CachedRepository {
+    pub fn new(inner: Arc<dyn Repository>, ttl: Duration, max_entries: usize) -> Self {
+        CachedRepository {
+            inner,
+            entries: Mutex::new(Entries { users: TtlMap::new(), pages: TtlMap::new(), next_seq: 0, generation: 0 }),
+            ttl,
+            max_entries,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: entries.len(),
+        }
+    }
+
+    /// Forgets the user and every page, since any of them may list it.
+    fn invalidate(&self, id: Uuid) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.generation += 1;
+        entries.users.remove(&id);
+        entries.pages.clear();
+    }
+
+    fn invalidate_all(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.generation += 1;
+        entries.users.clear();
+        entries.pages.clear();
+    }
+
+    fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn miss(&self) -> u64 {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.entries.lock().unwrap().generation
+    }
+}
+
+impl Subscriber for CachedRepository {
+    fn handle(&self, event: &UserEvent) {
+        self.invalidate(event.user_id());
+    }
+
+    fn purged(&self) {
+        self.invalidate_all();
+    }
+
+    fn resync(&self) {
+        self.invalidate_all();
+    }
+}
+
+#[async_trait]
+impl Repository for CachedRepository {
+    async fn insert(&self, user: DbUser, context: &ChangeContext) -> anyhow::Result<DbUser, InsertError> {
+        let id = user.id;
+        let res = self.inner.insert(user, context).await;
+        // also drops a cached `NotFound`
+        self.invalidate(id);
+        res
+    }
+
+    #[cfg(test)]
+    async fn fetch_all(&self) -> anyhow::Result<Vec<DbUser>, FetchAllError> {
+        self.inner.fetch_all().await
+    }
+
+    async fn fetch_page(&self, query: UserQuery) -> anyhow::Result<DbUserPage, FetchAllError> {
+        if let Some(page) = self.entries.lock().unwrap().pages.get(&query) {
+            self.hit();
+            return Ok(page);
+        }
+        let generation = self.miss();
+        let page = self.inner.fetch_page(query.clone()).await?;
+        let mut entries = self.entries.lock().unwrap();
+        if entries.generation == generation {
+            let seq = entries.next_seq();
+            entries.pages.insert(query, page.clone(), self.ttl, seq);
+            entries.evict(self.max_entries);
+        }
+        Ok(page)
+    }
+
+    async fn get(&self, id: UserId) -> anyhow::Result<DbUser, FetchOneError> {
+        let key = Uuid::from(id);
+        if let Some(user) = self.entries.lock().unwrap().users.get(&key) {
+            self.hit();
+            return user.ok_or(FetchOneError::NotFound);
+        }
+        let generation = self.miss();
+        let res = self.inner.get(id).await;
+        let cached = match &res {
+            Ok(user) => Some(user.clone()),
+            Err(FetchOneError::NotFound) => None,
+            Err(FetchOneError::Unknown) => return res,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        if entries.generation == generation {
+            let seq = entries.next_seq();
+            entries.users.insert(key, cached, self.ttl, seq);
+            entries.evict(self.max_entries);
+        }
+        res
+    }
+
+    async fn update(&self, id: UserId, new_db_user: DbUser, version: i64, context: &ChangeContext) -> anyhow::Result<DbUser, UpdateError> {
+        let res = self.inner.update(id, new_db_user, version, context).await;
+        self.invalidate(Uuid::from(id));
+        res
+    }
+
+    async fn delete(&self, id: UserId, version: i64, context: &ChangeContext) -> anyhow::Result<DbUser, DeleteError> {
+        let res = self.inner.delete(id, version, context).await;
+        self.invalidate(Uuid::from(id));
+        res
+    }
+
+    async fn restore(&self, id: UserId, context: &ChangeContext) -> anyhow::Result<DbUser, RestoreError> {
+        let res = self.inner.restore(id, context).await;
+        self.invalidate(Uuid::from(id));
+        res
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>, context: &ChangeContext) -> anyhow::Result<u64, PurgeError> {
+        let res = self.inner.purge(deleted_before, context).await;
+        self.invalidate_all();
+        res
+    }
+
+    async fn history(&self, id: UserId, query: HistoryQuery) -> anyhow::Result<DbChangePage, FetchAllError> {
+        self.inner.history(id, query).await
+    }
+
+    async fn as_of(&self, id: UserId, at: DateTime<Utc>) -> anyhow::Result<DbUser, FetchOneError> {
+        self.inner.as_of(id, at).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use chrono::{DateTime, NaiveDate, Utc};
+    use uuid::Uuid;
+    use crate::domain::entities::UserId;
+    use crate::domain::events::{Subscriber, UserEvent};
+    use crate::repository::cache::{CacheStats, CachedRepository};
+    use crate::repository::history::ChangeContext;
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::user::{DbUser, DbUserPage, DeleteError, FetchAllError, FetchOneError, InsertError, PurgeError, Repository, RestoreError, UpdateError, UserQuery};
+    use crate::repository::history::{DbChangePage, HistoryQuery};
+    use async_trait::async_trait;
+
+    /// Storage that cannot be reached, every read fails.
+    struct Unreachable {}
+
+    #[async_trait]
+    impl Repository for Unreachable {
+        async fn insert(&self, _user: DbUser, _context: &ChangeContext) -> anyhow::Result<DbUser, InsertError> {
+            Err(InsertError::Unknown)
+        }
+
+        #[cfg(test)]
+        async fn fetch_all(&self) -> anyhow::Result<Vec<DbUser>, FetchAllError> {
+            Err(FetchAllError::Unknown)
+        }
+
+        async fn fetch_page(&self, _query: UserQuery) -> anyhow::Result<DbUserPage, FetchAllError> {
+            Err(FetchAllError::Unknown)
+        }
+
+        async fn get(&self, _id: UserId) -> anyhow::Result<DbUser, FetchOneError> {
+            Err(FetchOneError::Unknown)
+        }
+
+        async fn update(&self, _id: UserId, _new_db_user: DbUser, _version: i64, _context: &ChangeContext) -> anyhow::Result<DbUser, UpdateError> {
+            Err(UpdateError::Unknown)
+        }
+
+        async fn delete(&self, _id: UserId, _version: i64, _context: &ChangeContext) -> anyhow::Result<DbUser, DeleteError> {
+            Err(DeleteError::Unknown)
+        }
+
+        async fn restore(&self, _id: UserId, _context: &ChangeContext) -> anyhow::Result<DbUser, RestoreError> {
+            Err(RestoreError::Unknown)
+        }
+
+        async fn purge(&self, _deleted_before: DateTime<Utc>, _context: &ChangeContext) -> anyhow::Result<u64, PurgeError> {
+            Err(PurgeError::Unknown)
+        }
+
+        async fn history(&self, _id: UserId, _query: HistoryQuery) -> anyhow::Result<DbChangePage, FetchAllError> {
+            Err(FetchAllError::Unknown)
+        }
+
+        async fn as_of(&self, _id: UserId, _at: DateTime<Utc>) -> anyhow::Result<DbUser, FetchOneError> {
+            Err(FetchOneError::Unknown)
+        }
+    }
+
+    fn user() -> DbUser {
+        DbUser {
+            id: Uuid::new_v4(),
+            first_name: "Hugo".to_string(),
+            last_name: "Muf".to_string(),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            city: "Nice".to_string(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+
+    fn cache(inner: &Arc<InMemoryRepository>, ttl: Duration, max_entries: usize) -> CachedRepository {
+        CachedRepository::new(inner.clone() as Arc<dyn Repository>, ttl, max_entries)
+    }
+
+    #[tokio::test]
+    async fn cache_reads_through_and_invalidates_on_writes() {
+        let inner = Arc::new(InMemoryRepository::new());
+        let repo = cache(&inner, Duration::from_secs(60), 100);
+        let id = Uuid::new_v4();
+
+        // the miss is remembered until the insert
+        assert_eq!(repo.get(UserId::from(id)).await.err().unwrap(), FetchOneError::NotFound);
+        assert_eq!(repo.get(UserId::from(id)).await.err().unwrap(), FetchOneError::NotFound);
+        let inserted = repo.insert(DbUser { id, ..user() }, &ChangeContext::test()).await.unwrap();
+        assert_eq!(repo.get(UserId::from(id)).await.unwrap(), inserted);
+        assert_eq!(repo.fetch_page(UserQuery::default()).await.unwrap().total, 1);
+        assert_eq!(repo.stats(), CacheStats { hits: 1, misses: 3, entries: 2 });
+
+        // changed behind the cache, still served from it
+        inner.update(UserId::from(id), DbUser { city: "Paris".to_string(), ..inserted.clone() }, 1, &ChangeContext::test()).await.unwrap();
+        assert_eq!(repo.get(UserId::from(id)).await.unwrap().city, "Nice");
+        let updated = repo.update(UserId::from(id), DbUser { city: "Lyon".to_string(), ..inserted.clone() }, 2, &ChangeContext::test()).await.unwrap();
+        assert_eq!(repo.get(UserId::from(id)).await.unwrap(), updated);
+
+        repo.delete(UserId::from(id), 3, &ChangeContext::test()).await.unwrap();
+        assert_eq!(repo.get(UserId::from(id)).await.err().unwrap(), FetchOneError::NotFound);
+        assert_eq!(repo.fetch_page(UserQuery::default()).await.unwrap().total, 0);
+    }
+
+    #[tokio::test]
+    async fn cache_forgets_users_changed_elsewhere() {
+        let inner = Arc::new(InMemoryRepository::new());
+        let repo = cache(&inner, Duration::from_secs(60), 100);
+        let inserted = inner.insert(user(), &ChangeContext::test()).await.unwrap();
+        assert_eq!(repo.fetch_page(UserQuery::default()).await.unwrap().total, 1);
+        repo.get(UserId::from(inserted.id)).await.unwrap();
+
+        let other = inner.insert(user(), &ChangeContext::test()).await.unwrap();
+        repo.handle(&UserEvent::created(&other, &ChangeContext::test()));
+        assert_eq!(repo.fetch_page(UserQuery::default()).await.unwrap().total, 2);
+        assert_eq!(repo.stats().misses, 3);
+
+        // a purge elsewhere has no event per user, everything is read again
+        repo.purged();
+        repo.get(UserId::from(inserted.id)).await.unwrap();
+        assert_eq!(repo.stats().misses, 4);
+
+        // so is a change feed gap, changes may have been missed
+        repo.resync();
+        repo.get(UserId::from(inserted.id)).await.unwrap();
+        assert_eq!(repo.stats().misses, 5);
+    }
+
+    #[tokio::test]
+    async fn cache_does_not_keep_failed_reads() {
+        let repo = CachedRepository::new(Arc::new(Unreachable {}), Duration::from_secs(60), 100);
+        let id = UserId::from(Uuid::new_v4());
+        assert_eq!(repo.get(id).await.err().unwrap(), FetchOneError::Unknown);
+        assert_eq!(repo.get(id).await.err().unwrap(), FetchOneError::Unknown);
+        assert!(repo.fetch_page(UserQuery::default()).await.is_err());
+        assert_eq!(repo.stats(), CacheStats { hits: 0, misses: 3, entries: 0 });
+    }
+
+    #[tokio::test]
+    async fn cache_shares_max_entries_between_users_and_pages() {
+        let inner = Arc::new(InMemoryRepository::new());
+        let first = inner.insert(user(), &ChangeContext::test()).await.unwrap();
+        let second = inner.insert(user(), &ChangeContext::test()).await.unwrap();
+
+        let repo = cache(&inner, Duration::from_secs(60), 2);
+        repo.get(UserId::from(first.id)).await.unwrap();
+        repo.fetch_page(UserQuery::default()).await.unwrap();
+        repo.get(UserId::from(second.id)).await.unwrap();
+        assert_eq!(repo.stats().entries, 2);
+        // the first user was the oldest entry, the page is still kept
+        repo.fetch_page(UserQuery::default()).await.unwrap();
+        repo.get(UserId::from(first.id)).await.unwrap();
+        assert_eq!(repo.stats(), CacheStats { hits: 1, misses: 4, entries: 2 });
+    }
+
+    #[tokio::test]
+    async fn cache_expires_and_evicts_entries() {
+        let inner = Arc::new(InMemoryRepository::new());
+        let first = inner.insert(user(), &ChangeContext::test()).await.unwrap();
+        let second = inner.insert(user(), &ChangeContext::test()).await.unwrap();
+
+        let repo = cache(&inner, Duration::from_secs(60), 1);
+        repo.get(UserId::from(first.id)).await.unwrap();
+        repo.get(UserId::from(second.id)).await.unwrap();
+        assert_eq!(repo.stats().entries, 1);
+        // the first was evicted, reading it again evicts the second
+        repo.get(UserId::from(first.id)).await.unwrap();
+        repo.get(UserId::from(second.id)).await.unwrap();
+        assert_eq!(repo.stats(), CacheStats { hits: 0, misses: 4, entries: 1 });
+
+        let repo = cache(&inner, Duration::from_millis(20), 10);
+        repo.get(UserId::from(first.id)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        repo.get(UserId::from(first.id)).await.unwrap();
+        assert_eq!(repo.stats().misses, 2);
+    }
+}
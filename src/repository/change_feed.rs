@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+use crate::domain::events::UserEvent;
+use crate::repository::user::PostgresRepository;
+
+/// Channel every instance listens on to hear about the changes made by the others.
+pub const CHANNEL: &str = "users_changed";
+
+/// Postgres refuses NOTIFY payloads from 8000 bytes on.
+const MAX_PAYLOAD: usize = 7999;
+
+/// Payload of a `users_changed` notification.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct ChangeNotification {
+    /// Instance that made the change, it already published the event to its own subscribers.
+    pub origin: Uuid,
+    /// Outbox message holding the event, none for a purge.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<i64>,
+    /// The event itself unless it made the payload too long, listeners then read it from the outbox.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<UserEvent>,
+    /// Deleted users were purged, which has no event nor outbox message.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub purge: bool,
+}
+
+/// Announces the event once the transaction commits, nothing is sent if it rolls back.
+pub(crate) async fn notify(tx: &mut Transaction<'_, Postgres>, origin: Uuid, message_id: i64, event: &UserEvent) -> Result<(), sqlx::Error> {
+    let mut notification = ChangeNotification { origin, message_id: Some(message_id), event: Some(event.clone()), purge: false };
+    let mut payload = serde_json::to_string(&notification).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+    if payload.len() > MAX_PAYLOAD {
+        notification.event = None;
+        payload = serde_json::to_string(&notification).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+    }
+    send(tx, payload).await
+}
+
+/// Announces a purge once the transaction commits, so the other instances drop what they cached.
+pub(crate) async fn notify_purge(tx: &mut Transaction<'_, Postgres>, origin: Uuid) -> Result<(), sqlx::Error> {
+    let notification = ChangeNotification { origin, message_id: None, event: None, purge: true };
+    let payload = serde_json::to_string(&notification).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+    send(tx, payload).await
+}
+
+async fn send(tx: &mut Transaction<'_, Postgres>, payload: String) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(CHANNEL)
+        .bind(payload)
+        .execute(&mut *tx).await?;
+    Ok(())
+}
+
+impl PostgresRepository {
+    /// The event of an outbox message, for notifications sent without it.
+    pub async fn outbox_event(&self, message_id: i64) -> Result<Option<UserEvent>, sqlx::Error> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        let event: Option<Json<UserEvent>> = sqlx::query_scalar("SELECT payload FROM user_outbox WHERE id = $1")
+            .bind(message_id)
+            .fetch_optional(db_pool).await?;
+        Ok(event.map(|event| event.0))
+    }
+}
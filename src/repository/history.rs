@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::{FromRow, Postgres, Row, Transaction};
+use uuid::Uuid;
+use crate::repository::user::DbUser;
+
+/// Who asked for a change, recorded with it in the user history.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct ChangeContext {
+    pub actor: String,
+    pub request_id: String,
+}
+
+#[cfg(test)]
+impl ChangeContext {
+    pub fn test() -> Self {
+        ChangeContext { actor: "test".to_string(), request_id: "test".to_string() }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Insert,
+    Update,
+    Delete,
+    Restore,
+    /// The user was removed for good, its snapshots in earlier entries are blanked.
+    Purge,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Insert => "insert",
+            Operation::Update => "update",
+            Operation::Delete => "delete",
+            Operation::Restore => "restore",
+            Operation::Purge => "purge",
+        }
+    }
+}
+
+impl std::str::FromStr for Operation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "insert" => Ok(Operation::Insert),
+            "update" => Ok(Operation::Update),
+            "delete" => Ok(Operation::Delete),
+            "restore" => Ok(Operation::Restore),
+            "purge" => Ok(Operation::Purge),
+            _ => Err(()),
+        }
+    }
+}
+
+/// One append-only history entry, `old` is `None` for an insert and both are `None` once the user is purged.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct DbChange {
+    pub seq: i64,
+    pub user_id: Uuid,
+    pub operation: Operation,
+    pub old: Option<DbUser>,
+    pub new: Option<DbUser>,
+    pub actor: String,
+    pub request_id: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbChangePage {
+    pub changes: Vec<DbChange>,
+    pub total: i64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HistoryQuery {
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        HistoryQuery { limit: 20, offset: 0 }
+    }
+}
+
+impl<'r> FromRow<'r, PgRow> for DbChange {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let operation: String = row.try_get("operation")?;
+        let operation = operation.parse().map_err(|_| sqlx::Error::ColumnDecode {
+            index: "operation".to_string(),
+            source: format!("unknown operation {}", operation).into(),
+        })?;
+        let old: Option<Json<DbUser>> = row.try_get("old_values")?;
+        let new: Option<Json<DbUser>> = row.try_get("new_values")?;
+        Ok(DbChange {
+            seq: row.try_get("seq")?,
+            user_id: row.try_get("user_id")?,
+            operation,
+            old: old.map(|json| json.0),
+            new: new.map(|json| json.0),
+            actor: row.try_get("actor")?,
+            request_id: row.try_get("request_id")?,
+            changed_at: row.try_get("changed_at")?,
+        })
+    }
+}
+
+/// Appends the entry closing the history of a purged user, without values.
+pub(crate) async fn purged(tx: &mut Transaction<'_, Postgres>, user_id: Uuid, context: &ChangeContext) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO user_history (user_id, operation, actor, request_id) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(Operation::Purge.as_str())
+        .bind(&context.actor)
+        .bind(&context.request_id)
+        .execute(&mut *tx).await?;
+    Ok(())
+}
+
+/// Appends a history entry in the transaction that made the change, so both commit or neither does.
+pub(crate) async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    operation: Operation,
+    old: Option<&DbUser>,
+    new: Option<&DbUser>,
+    context: &ChangeContext,
+) -> Result<(), sqlx::Error> {
+    let user_id = new.or(old).map(|user| user.id);
+    sqlx::query("INSERT INTO user_history (user_id, operation, old_values, new_values, actor, request_id) VALUES ($1, $2, $3, $4, $5, $6)")
+        .bind(user_id)
+        .bind(operation.as_str())
+        .bind(old.map(Json))
+        .bind(new.map(Json))
+        .bind(&context.actor)
+        .bind(&context.request_id)
+        .execute(&mut *tx).await?;
+    Ok(())
+}
@@ -0,0 +1,700 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, SubsecRound, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+use crate::domain::entities::UserId;
+use crate::repository::history::{ChangeContext, DbChange, DbChangePage, HistoryQuery, Operation};
+use crate::repository::outbox::{event, Outbox, OutboxError, OutboxMessage};
+use crate::repository::webhook::{DbDelivery, DbDeliveryPage, DbWebhook, DeliveryStatus, PendingDelivery, WebhookError, WebhookRepository};
+use crate::repository::user::{DbUser, DbUserPage, DeleteError, FetchAllError, FetchOneError, InsertError, PurgeError, Repository, RestoreError, SortField, SortOrder, UpdateError, UserQuery};
+
+#[derive(Debug, Default)]
+pub struct InMemoryRepository {
+    users: RwLock<HashMap<Uuid, DbUser>>,
+    history: RwLock<Vec<DbChange>>,
+    outbox: RwLock<Vec<OutboxEntry>>,
+    webhooks: RwLock<Vec<DbWebhook>>,
+    deliveries: RwLock<Vec<DbDelivery>>,
+}
+
+#[derive(Debug)]
+struct OutboxEntry {
+    message: OutboxMessage,
+    next_attempt_at: DateTime<Utc>,
+    published_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> InMemoryRepository {
+        Self {
+            users: RwLock::new(HashMap::new()),
+            history: RwLock::new(Vec::new()),
+            outbox: RwLock::new(Vec::new()),
+            webhooks: RwLock::new(Vec::new()),
+            deliveries: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Called with the users lock held so entries are appended in the order the changes happened.
+    fn record(&self, operation: Operation, old: Option<DbUser>, new: &DbUser, context: &ChangeContext) -> Result<(), ()> {
+        let mut history = self.history.write().map_err(|_| ())?;
+        let mut outbox = self.outbox.write().map_err(|_| ())?;
+        let event = event(operation, old.as_ref(), new, context);
+        let id = outbox.len() as i64 + 1;
+        outbox.push(OutboxEntry {
+            message: OutboxMessage {
+                id,
+                user_id: new.id,
+                event,
+                attempts: 0,
+                created_at: new.updated_at,
+            },
+            next_attempt_at: new.updated_at,
+            published_at: None,
+            last_error: None,
+        });
+        let seq = history.len() as i64 + 1;
+        history.push(DbChange {
+            seq,
+            user_id: new.id,
+            operation,
+            old,
+            new: Some(new.clone()),
+            actor: context.actor.clone(),
+            request_id: context.request_id.clone(),
+            changed_at: new.updated_at,
+        });
+        Ok(())
+    }
+}
+
+/// Current time at the microsecond precision of a Postgres `TIMESTAMPTZ`.
+fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(6)
+}
+
+fn matches(user: &DbUser, query: &UserQuery) -> bool {
+    (query.include_deleted || user.deleted_at.is_none())
+        && query.city.as_ref().is_none_or(|city| &user.city == city)
+        && query.last_name_prefix.as_ref().is_none_or(|prefix| user.last_name.starts_with(prefix.as_str()))
+        && query.birthday_from.is_none_or(|from| user.birthday_date >= from)
+        && query.birthday_to.is_none_or(|to| user.birthday_date <= to)
+        && query.updated_since.is_none_or(|since| user.updated_at >= since)
+}
+
+fn after(user: &DbUser, query: &UserQuery) -> bool {
+    match &query.after {
+        None => true,
+        Some(cursor) => {
+            let position = (query.sort.value(user), user.id).cmp(&(cursor.value.clone(), cursor.id));
+            match query.order {
+                SortOrder::Asc => position == Ordering::Greater,
+                SortOrder::Desc => position == Ordering::Less,
+            }
+        }
+    }
+}
+
+fn compare(a: &DbUser, b: &DbUser, sort: SortField) -> Ordering {
+    let ordering = match sort {
+        SortField::Id => Ordering::Equal,
+        SortField::FirstName => a.first_name.cmp(&b.first_name),
+        SortField::LastName => a.last_name.cmp(&b.last_name),
+        SortField::BirthdayDate => a.birthday_date.cmp(&b.birthday_date),
+        SortField::City => a.city.cmp(&b.city),
+        SortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+    };
+    ordering.then_with(|| a.id.cmp(&b.id))
+}
+
+#[async_trait]
+impl Repository for InMemoryRepository {
+    async fn insert(&self, db_user: DbUser, context: &ChangeContext) -> anyhow::Result<DbUser, InsertError> {
+        let mut users = match self.users.write() {
+            Ok(users) => users,
+            Err(_) => return Err(InsertError::Unknown),
+        };
+        if users.contains_key(&db_user.id) {
+            return Err(InsertError::Conflict);
+        }
+        let now = now();
+        let db_user = DbUser { version: 1, created_at: now, updated_at: now, deleted_at: None, ..db_user };
+        self.record(Operation::Insert, None, &db_user, context).map_err(|_| InsertError::Unknown)?;
+        users.insert(db_user.id, db_user.clone());
+        Ok(db_user)
+    }
+
+    #[cfg(test)]
+    async fn fetch_all(&self) -> anyhow::Result<Vec<DbUser>, FetchAllError> {
+        match self.users.read() {
+            Ok(users) => Ok(users.values().filter(|user| user.deleted_at.is_none()).cloned().collect()),
+            Err(_) => Err(FetchAllError::Unknown),
+        }
+    }
+
+    async fn fetch_page(&self, query: UserQuery) -> anyhow::Result<DbUserPage, FetchAllError> {
+        let users = match self.users.read() {
+            Ok(users) => users,
+            Err(_) => return Err(FetchAllError::Unknown),
+        };
+        let mut filtered: Vec<DbUser> = users.values()
+            .filter(|user| matches(user, &query))
+            .cloned()
+            .collect();
+        filtered.sort_by(|a, b| match query.order {
+            SortOrder::Asc => compare(a, b, query.sort),
+            SortOrder::Desc => compare(b, a, query.sort),
+        });
+        let total = filtered.len() as i64;
+        let users = filtered.into_iter()
+            .filter(|user| after(user, &query))
+            .skip(query.offset.max(0) as usize)
+            .take(query.limit.max(0) as usize)
+            .collect();
+        Ok(DbUserPage { users, total })
+    }
+
+    async fn get(&self, id: UserId) -> anyhow::Result<DbUser, FetchOneError> {
+        let users = match self.users.read() {
+            Ok(users) => users,
+            Err(_) => return Err(FetchOneError::Unknown),
+        };
+        match users.get(&Uuid::from(id)).filter(|user| user.deleted_at.is_none()) {
+            Some(user) => Ok(user.clone()),
+            None => Err(FetchOneError::NotFound),
+        }
+    }
+
+    async fn update(
+        &self,
+        id: UserId,
+        new_db_user: DbUser,
+        version: i64,
+        context: &ChangeContext,
+    ) -> anyhow::Result<DbUser, UpdateError> {
+        let mut users = match self.users.write() {
+            Ok(users) => users,
+            Err(_) => return Err(UpdateError::Unknown),
+        };
+        match users.get_mut(&Uuid::from(id)).filter(|user| user.deleted_at.is_none()) {
+            Some(user) if user.version != version => Err(UpdateError::VersionMismatch),
+            Some(user) => {
+                let old = user.clone();
+                user.version += 1;
+                user.first_name = new_db_user.first_name;
+                user.last_name = new_db_user.last_name;
+                user.birthday_date = new_db_user.birthday_date;
+                user.city = new_db_user.city;
+                user.updated_at = now();
+                self.record(Operation::Update, Some(old), user, context).map_err(|_| UpdateError::Unknown)?;
+                Ok(user.clone())
+            }
+            None => Err(UpdateError::NotFound),
+        }
+    }
+
+    async fn delete(&self, id: UserId, version: i64, context: &ChangeContext) -> anyhow::Result<DbUser, DeleteError> {
+        let mut users = match self.users.write() {
+            Ok(users) => users,
+            Err(_) => return Err(DeleteError::Unknown),
+        };
+        match users.get_mut(&Uuid::from(id)).filter(|user| user.deleted_at.is_none()) {
+            Some(user) if user.version != version => Err(DeleteError::VersionMismatch),
+            Some(user) => {
+                let old = user.clone();
+                let now = now();
+                user.version += 1;
+                user.updated_at = now;
+                user.deleted_at = Some(now);
+                self.record(Operation::Delete, Some(old), user, context).map_err(|_| DeleteError::Unknown)?;
+                Ok(user.clone())
+            }
+            None => Err(DeleteError::NotFound),
+        }
+    }
+
+    async fn restore(&self, id: UserId, context: &ChangeContext) -> anyhow::Result<DbUser, RestoreError> {
+        let mut users = match self.users.write() {
+            Ok(users) => users,
+            Err(_) => return Err(RestoreError::Unknown),
+        };
+        match users.get_mut(&Uuid::from(id)) {
+            Some(user) if user.deleted_at.is_none() => Err(RestoreError::NotDeleted),
+            Some(user) => {
+                let old = user.clone();
+                user.version += 1;
+                user.updated_at = now();
+                user.deleted_at = None;
+                self.record(Operation::Restore, Some(old), user, context).map_err(|_| RestoreError::Unknown)?;
+                Ok(user.clone())
+            }
+            None => Err(RestoreError::NotFound),
+        }
+    }
+
+    async fn purge(&self, deleted_before: DateTime<Utc>, context: &ChangeContext) -> anyhow::Result<u64, PurgeError> {
+        let mut users = match self.users.write() {
+            Ok(users) => users,
+            Err(_) => return Err(PurgeError::Unknown),
+        };
+        let mut history = self.history.write().map_err(|_| PurgeError::Unknown)?;
+        let purged: Vec<Uuid> = users.values()
+            .filter(|user| user.deleted_at.is_some_and(|deleted_at| deleted_at < deleted_before))
+            .map(|user| user.id)
+            .collect();
+        let changed_at = now();
+        for id in &purged {
+            users.remove(id);
+            for change in history.iter_mut().filter(|change| change.user_id == *id) {
+                change.old = None;
+                change.new = None;
+            }
+            let seq = history.len() as i64 + 1;
+            history.push(DbChange {
+                seq,
+                user_id: *id,
+                operation: Operation::Purge,
+                old: None,
+                new: None,
+                actor: context.actor.clone(),
+                request_id: context.request_id.clone(),
+                changed_at,
+            });
+        }
+        Ok(purged.len() as u64)
+    }
+
+    async fn history(&self, id: UserId, query: HistoryQuery) -> anyhow::Result<DbChangePage, FetchAllError> {
+        let history = match self.history.read() {
+            Ok(history) => history,
+            Err(_) => return Err(FetchAllError::Unknown),
+        };
+        let id = Uuid::from(id);
+        let changes: Vec<&DbChange> = history.iter().filter(|change| change.user_id == id).collect();
+        Ok(DbChangePage {
+            total: changes.len() as i64,
+            changes: changes.into_iter()
+                .skip(query.offset.max(0) as usize)
+                .take(query.limit.max(0) as usize)
+                .cloned()
+                .collect(),
+        })
+    }
+
+    async fn as_of(&self, id: UserId, at: DateTime<Utc>) -> anyhow::Result<DbUser, FetchOneError> {
+        let history = match self.history.read() {
+            Ok(history) => history,
+            Err(_) => return Err(FetchOneError::Unknown),
+        };
+        let id = Uuid::from(id);
+        history.iter().rev()
+            .find(|change| change.user_id == id && change.changed_at <= at)
+            .and_then(|change| change.new.clone())
+            .filter(|user| user.deleted_at.is_none())
+            .ok_or(FetchOneError::NotFound)
+    }
+}
+
+#[async_trait]
+impl Outbox for InMemoryRepository {
+    async fn claim(&self, limit: i64, lease: Duration) -> anyhow::Result<Vec<OutboxMessage>, OutboxError> {
+        let mut outbox = self.outbox.write().map_err(|_| OutboxError::Unknown)?;
+        let now = now();
+        let lease = chrono::Duration::from_std(lease).map_err(|_| OutboxError::Unknown)?;
+        Ok(outbox.iter_mut()
+            .filter(|entry| entry.published_at.is_none() && entry.next_attempt_at <= now)
+            .take(limit as usize)
+            .map(|entry| {
+                entry.next_attempt_at = now + lease;
+                entry.message.clone()
+            })
+            .collect())
+    }
+
+    async fn mark_published(&self, id: i64) -> anyhow::Result<(), OutboxError> {
+        let mut outbox = self.outbox.write().map_err(|_| OutboxError::Unknown)?;
+        match outbox.iter_mut().find(|entry| entry.message.id == id) {
+            Some(entry) => {
+                entry.published_at = Some(now());
+                Ok(())
+            }
+            None => Err(OutboxError::Unknown),
+        }
+    }
+
+    async fn mark_failed(&self, id: i64, error: &str, retry_at: DateTime<Utc>) -> anyhow::Result<(), OutboxError> {
+        let mut outbox = self.outbox.write().map_err(|_| OutboxError::Unknown)?;
+        match outbox.iter_mut().find(|entry| entry.message.id == id) {
+            Some(entry) => {
+                entry.message.attempts += 1;
+                entry.last_error = Some(error.to_string());
+                // stored at the precision of a Postgres `TIMESTAMPTZ`
+                entry.next_attempt_at = retry_at.trunc_subsecs(6);
+                Ok(())
+            }
+            None => Err(OutboxError::Unknown),
+        }
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for InMemoryRepository {
+    async fn insert_webhook(&self, webhook: DbWebhook) -> anyhow::Result<DbWebhook, WebhookError> {
+        let mut webhooks = self.webhooks.write().map_err(|_| WebhookError::Unknown)?;
+        let webhook = DbWebhook { active: true, consecutive_failures: 0, disabled_at: None, created_at: now(), ..webhook };
+        webhooks.push(webhook.clone());
+        Ok(webhook)
+    }
+
+    async fn fetch_webhooks(&self) -> anyhow::Result<Vec<DbWebhook>, WebhookError> {
+        Ok(self.webhooks.read().map_err(|_| WebhookError::Unknown)?.clone())
+    }
+
+    async fn get_webhook(&self, id: Uuid) -> anyhow::Result<DbWebhook, WebhookError> {
+        let webhooks = self.webhooks.read().map_err(|_| WebhookError::Unknown)?;
+        webhooks.iter().find(|webhook| webhook.id == id).cloned().ok_or(WebhookError::NotFound)
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> anyhow::Result<(), WebhookError> {
+        let mut webhooks = self.webhooks.write().map_err(|_| WebhookError::Unknown)?;
+        let mut deliveries = self.deliveries.write().map_err(|_| WebhookError::Unknown)?;
+        let count = webhooks.len();
+        webhooks.retain(|webhook| webhook.id != id);
+        if webhooks.len() == count {
+            return Err(WebhookError::NotFound);
+        }
+        deliveries.retain(|delivery| delivery.subscription_id != id);
+        Ok(())
+    }
+
+    async fn queue_deliveries(&self, message_id: i64, event_type: &str, payload: &Value) -> anyhow::Result<(), WebhookError> {
+        let webhooks = self.webhooks.read().map_err(|_| WebhookError::Unknown)?;
+        let mut deliveries = self.deliveries.write().map_err(|_| WebhookError::Unknown)?;
+        let now = now();
+        for webhook in webhooks.iter().filter(|webhook| webhook.active && webhook.listens_to(event_type)) {
+            if deliveries.iter().any(|delivery| delivery.subscription_id == webhook.id && delivery.message_id == message_id) {
+                continue;
+            }
+            let id = deliveries.iter().map(|delivery| delivery.id).max().unwrap_or(0) + 1;
+            deliveries.push(DbDelivery {
+                id,
+                subscription_id: webhook.id,
+                message_id,
+                event_type: event_type.to_string(),
+                payload: payload.clone(),
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                response_status: None,
+                last_error: None,
+                next_attempt_at: now,
+                delivered_at: None,
+                created_at: now,
+            });
+        }
+        Ok(())
+    }
+
+    async fn claim_deliveries(&self, limit: i64, lease: Duration) -> anyhow::Result<Vec<PendingDelivery>, WebhookError> {
+        let webhooks = self.webhooks.read().map_err(|_| WebhookError::Unknown)?;
+        let mut deliveries = self.deliveries.write().map_err(|_| WebhookError::Unknown)?;
+        let now = now();
+        let lease = chrono::Duration::from_std(lease).map_err(|_| WebhookError::Unknown)?;
+        Ok(deliveries.iter_mut()
+            .filter(|delivery| delivery.status == DeliveryStatus::Pending && delivery.next_attempt_at <= now)
+            .filter_map(|delivery| {
+                let webhook = webhooks.iter().find(|webhook| webhook.id == delivery.subscription_id && webhook.active)?;
+                delivery.next_attempt_at = now + lease;
+                Some(PendingDelivery { delivery: delivery.clone(), url: webhook.url.clone(), secret: webhook.secret.clone() })
+            })
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn mark_delivered(&self, id: i64, response_status: i32) -> anyhow::Result<(), WebhookError> {
+        let mut webhooks = self.webhooks.write().map_err(|_| WebhookError::Unknown)?;
+        let mut deliveries = self.deliveries.write().map_err(|_| WebhookError::Unknown)?;
+        let delivery = deliveries.iter_mut().find(|delivery| delivery.id == id).ok_or(WebhookError::NotFound)?;
+        delivery.status = DeliveryStatus::Delivered;
+        delivery.attempts += 1;
+        delivery.response_status = Some(response_status);
+        delivery.last_error = None;
+        delivery.delivered_at = Some(now());
+        if let Some(webhook) = webhooks.iter_mut().find(|webhook| webhook.id == delivery.subscription_id) {
+            webhook.consecutive_failures = 0;
+        }
+        Ok(())
+    }
+
+    async fn mark_delivery_failed(
+        &self,
+        id: i64,
+        response_status: Option<i32>,
+        error: &str,
+        retry_at: DateTime<Utc>,
+        max_failures: i32,
+    ) -> anyhow::Result<bool, WebhookError> {
+        let mut webhooks = self.webhooks.write().map_err(|_| WebhookError::Unknown)?;
+        let mut deliveries = self.deliveries.write().map_err(|_| WebhookError::Unknown)?;
+        let delivery = deliveries.iter_mut().find(|delivery| delivery.id == id).ok_or(WebhookError::NotFound)?;
+        delivery.attempts += 1;
+        delivery.response_status = response_status;
+        delivery.last_error = Some(error.to_string());
+        delivery.next_attempt_at = retry_at.trunc_subsecs(6);
+        let subscription_id = delivery.subscription_id;
+        let webhook = webhooks.iter_mut().find(|webhook| webhook.id == subscription_id).ok_or(WebhookError::NotFound)?;
+        webhook.consecutive_failures += 1;
+        if webhook.consecutive_failures < max_failures {
+            return Ok(false);
+        }
+        if webhook.active {
+            webhook.active = false;
+            webhook.disabled_at = Some(now());
+        }
+        deliveries.iter_mut()
+            .filter(|delivery| delivery.subscription_id == subscription_id && delivery.status == DeliveryStatus::Pending)
+            .for_each(|delivery| delivery.status = DeliveryStatus::Cancelled);
+        Ok(true)
+    }
+
+    async fn fetch_deliveries(&self, subscription_id: Uuid, limit: i64, offset: i64) -> anyhow::Result<DbDeliveryPage, WebhookError> {
+        let deliveries = self.deliveries.read().map_err(|_| WebhookError::Unknown)?;
+        let matching: Vec<&DbDelivery> = deliveries.iter().rev()
+            .filter(|delivery| delivery.subscription_id == subscription_id)
+            .collect();
+        Ok(DbDeliveryPage {
+            total: matching.len() as i64,
+            deliveries: matching.into_iter().skip(offset as usize).take(limit as usize).cloned().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::history::{ChangeContext, HistoryQuery, Operation};
+    use crate::domain::entities::UserId;
+    use chrono::{NaiveDate, Utc};
+    use random_string::generate;
+    use std::thread;
+    use std::time::Duration;
+    use uuid::Uuid;
+    use crate::domain::events::UserEvent;
+    use crate::repository::memory::InMemoryRepository;
+    use crate::repository::outbox::Outbox;
+    use crate::repository::user::{DbUser, DeleteError, FetchOneError, InsertError, Repository, RestoreError, SortField, SortOrder, UpdateError, UserQuery};
+
+    fn user(id: Uuid) -> DbUser {
+        let charset = "abcdefghijkl";
+        DbUser {
+            id,
+            last_name: generate(6, charset),
+            first_name: generate(6, charset),
+            city: generate(6, charset),
+            birthday_date: NaiveDate::from_ymd_opt(2015, 3, 14).unwrap(),
+            version: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn create_works() {
+        let repo = InMemoryRepository::new();
+        let user = user(Uuid::new_v4());
+        let expected = user.clone();
+        let res = repo.insert(user, &ChangeContext::test()).await.unwrap();
+        assert_eq!(res, DbUser { created_at: res.created_at, updated_at: res.updated_at, ..expected });
+        assert_eq!(res.created_at, res.updated_at);
+    }
+
+    #[tokio::test]
+    async fn create_fail() {
+        let repo = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        repo.insert(user(id), &ChangeContext::test()).await.unwrap();
+        let res = repo.insert(user(id), &ChangeContext::test()).await;
+        assert_eq!(res.err().unwrap(), InsertError::Conflict)
+    }
+
+    #[tokio::test]
+    async fn get_work() {
+        let repo = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        let expected = repo.insert(user(id), &ChangeContext::test()).await.unwrap();
+        let res = repo.get(UserId::from(id)).await.unwrap();
+        assert_eq!(res, expected)
+    }
+
+    #[tokio::test]
+    async fn get_not_found() {
+        let repo = InMemoryRepository::new();
+        let res = repo.get(UserId::from(Uuid::new_v4())).await;
+        assert_eq!(res.err().unwrap(), FetchOneError::NotFound)
+    }
+
+    #[tokio::test]
+    async fn list_users_work() {
+        let repo = InMemoryRepository::new();
+        let mut users = Vec::new();
+        for _ in 1..10 {
+            users.push(repo.insert(user(Uuid::new_v4()), &ChangeContext::test()).await.unwrap());
+        }
+        let res = repo.fetch_all().await.unwrap();
+        assert_eq!(res.len(), users.len());
+        for expected_user in users {
+            assert!(res.contains(&expected_user));
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_page_work() {
+        let repo = InMemoryRepository::new();
+        for n in 1..10 {
+            let mut user = user(Uuid::new_v4());
+            user.city = if n % 2 == 0 { "nice".to_string() } else { "paris".to_string() };
+            user.last_name = format!("muf{}", n);
+            repo.insert(user, &ChangeContext::test()).await.unwrap();
+        }
+        let query = UserQuery {
+            limit: 2,
+            offset: 1,
+            city: Some("paris".to_string()),
+            last_name_prefix: Some("muf".to_string()),
+            sort: SortField::LastName,
+            order: SortOrder::Desc,
+            ..UserQuery::default()
+        };
+        let res = repo.fetch_page(query).await.unwrap();
+        assert_eq!(res.total, 5);
+        let last_names: Vec<String> = res.users.into_iter().map(|x| x.last_name).collect();
+        assert_eq!(last_names, vec!["muf7".to_string(), "muf5".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn fetch_page_updated_since() {
+        let repo = InMemoryRepository::new();
+        let changed = repo.insert(user(Uuid::new_v4()), &ChangeContext::test()).await.unwrap();
+        repo.insert(user(Uuid::new_v4()), &ChangeContext::test()).await.unwrap();
+        thread::sleep(Duration::from_millis(2));
+        let since = Utc::now();
+        let changed = repo.update(UserId::from(changed.id), changed.clone(), 1, &ChangeContext::test()).await.unwrap();
+        let query = UserQuery {
+            updated_since: Some(since),
+            sort: SortField::UpdatedAt,
+            ..UserQuery::default()
+        };
+        let res = repo.fetch_page(query).await.unwrap();
+        assert_eq!(res.total, 1);
+        assert_eq!(res.users, vec![changed]);
+    }
+
+    #[tokio::test]
+    async fn delete_work() {
+        let repo = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        repo.insert(user(id), &ChangeContext::test()).await.unwrap();
+        let res0 = repo.delete(UserId::from(id), 2, &ChangeContext::test()).await;
+        let res1 = repo.delete(UserId::from(id), 1, &ChangeContext::test()).await;
+        let res2 = repo.get(UserId::from(id)).await;
+        let res3 = repo.delete(UserId::from(id), 1, &ChangeContext::test()).await;
+        assert_eq!(res0.err().unwrap(), DeleteError::VersionMismatch);
+        let deleted = res1.unwrap();
+        assert_eq!(deleted.version, 2);
+        assert!(deleted.deleted_at.is_some());
+        assert_eq!(res2.err().unwrap(), FetchOneError::NotFound);
+        assert_eq!(res3.err().unwrap(), DeleteError::NotFound)
+    }
+
+    #[tokio::test]
+    async fn restore_work() {
+        let repo = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        repo.insert(user(id), &ChangeContext::test()).await.unwrap();
+        let live = repo.restore(UserId::from(id), &ChangeContext::test()).await;
+        repo.delete(UserId::from(id), 1, &ChangeContext::test()).await.unwrap();
+        let res = repo.restore(UserId::from(id), &ChangeContext::test()).await.unwrap();
+        assert_eq!(live.err().unwrap(), RestoreError::NotDeleted);
+        assert_eq!(res.deleted_at, None);
+        assert_eq!(res.version, 3);
+        assert_eq!(repo.get(UserId::from(id)).await.unwrap(), res);
+        assert_eq!(repo.restore(UserId::from(Uuid::new_v4()), &ChangeContext::test()).await.err().unwrap(), RestoreError::NotFound);
+    }
+
+    #[tokio::test]
+    async fn purge_work() {
+        let repo = InMemoryRepository::new();
+        let kept = repo.insert(user(Uuid::new_v4()), &ChangeContext::test()).await.unwrap();
+        let deleted = repo.insert(user(Uuid::new_v4()), &ChangeContext::test()).await.unwrap();
+        repo.delete(UserId::from(deleted.id), 1, &ChangeContext::test()).await.unwrap();
+        assert_eq!(repo.purge(Utc::now() - chrono::Duration::days(1), &ChangeContext::test()).await.unwrap(), 0);
+        thread::sleep(Duration::from_millis(2));
+        assert_eq!(repo.purge(Utc::now(), &ChangeContext::test()).await.unwrap(), 1);
+        assert_eq!(repo.restore(UserId::from(deleted.id), &ChangeContext::test()).await.err().unwrap(), RestoreError::NotFound);
+        assert_eq!(repo.get(UserId::from(kept.id)).await.unwrap(), kept);
+
+        // the history tells the user was purged but no longer holds its values
+        let history = repo.history(UserId::from(deleted.id), HistoryQuery::default()).await.unwrap();
+        let operations: Vec<Operation> = history.changes.iter().map(|change| change.operation).collect();
+        assert_eq!(operations, vec![Operation::Insert, Operation::Delete, Operation::Purge]);
+        assert!(history.changes.iter().all(|change| change.old.is_none() && change.new.is_none()));
+        assert!(repo.history(UserId::from(kept.id), HistoryQuery::default()).await.unwrap().changes[0].new.is_some());
+    }
+
+    #[tokio::test]
+    async fn outbox_work() {
+        let repo = InMemoryRepository::new();
+        let inserted = repo.insert(user(Uuid::new_v4()), &ChangeContext::test()).await.unwrap();
+        repo.update(UserId::from(inserted.id), DbUser { city: "Paris".to_string(), ..inserted.clone() }, 1, &ChangeContext::test()).await.unwrap();
+        let claimed = repo.claim(10, Duration::from_secs(60)).await.unwrap();
+        assert_eq!(claimed.iter().map(|message| message.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(matches!(claimed[1].event, UserEvent::UserUpdated(_)));
+        // leased to this relay
+        assert!(repo.claim(10, Duration::from_secs(60)).await.unwrap().is_empty());
+        repo.mark_published(1).await.unwrap();
+        repo.mark_failed(2, "unavailable", Utc::now()).await.unwrap();
+        let claimed = repo.claim(10, Duration::from_secs(60)).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_page_include_deleted() {
+        let repo = InMemoryRepository::new();
+        repo.insert(user(Uuid::new_v4()), &ChangeContext::test()).await.unwrap();
+        let deleted = repo.insert(user(Uuid::new_v4()), &ChangeContext::test()).await.unwrap();
+        repo.delete(UserId::from(deleted.id), 1, &ChangeContext::test()).await.unwrap();
+        let res = repo.fetch_page(UserQuery::default()).await.unwrap();
+        assert_eq!(res.total, 1);
+        assert_eq!(repo.fetch_all().await.unwrap().len(), 1);
+        let res = repo.fetch_page(UserQuery { include_deleted: true, ..UserQuery::default() }).await.unwrap();
+        assert_eq!(res.total, 2);
+        assert!(res.users.iter().any(|x| x.id == deleted.id && x.deleted_at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn update_work() {
+        let repo = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        let inserted = repo.insert(user(id), &ChangeContext::test()).await.unwrap();
+        let user_update = user(id);
+        let res = repo.update(UserId::from(id), user_update.clone(), 1, &ChangeContext::test()).await.unwrap();
+        let expected = DbUser { version: 2, created_at: inserted.created_at, updated_at: res.updated_at, ..user_update.clone() };
+        assert_eq!(res, expected);
+        assert!(res.updated_at >= inserted.updated_at);
+        let stale = repo.update(UserId::from(id), user_update, 1, &ChangeContext::test()).await;
+        assert_eq!(stale.err().unwrap(), UpdateError::VersionMismatch);
+    }
+
+    #[tokio::test]
+    async fn update_unknown() {
+        let repo = InMemoryRepository::new();
+        let id = Uuid::new_v4();
+        let res = repo.update(UserId::from(id), user(id), 1, &ChangeContext::test()).await;
+        assert_eq!(res.err().unwrap(), UpdateError::NotFound);
+    }
+}
@@ -1 +1,8 @@
-pub mod user;
\ No newline at end of file
+pub mod user;
+pub mod memory;
+pub mod migration;
+pub mod history;
+pub mod outbox;
+pub mod change_feed;
+pub mod cache;
+pub mod webhook;
\ No newline at end of file
@@ -0,0 +1,114 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::{FromRow, Postgres, Row, Transaction};
+use uuid::Uuid;
+use crate::domain::events::UserEvent;
+use crate::repository::history::{ChangeContext, Operation};
+use crate::repository::user::{DbUser, PostgresRepository};
+
+/// An event waiting to be delivered, `id` is the key downstream consumers deduplicate on.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct OutboxMessage {
+    pub id: i64,
+    pub user_id: Uuid,
+    pub event: UserEvent,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub enum OutboxError {
+    Unknown,
+}
+
+/// Events written with the change they describe, drained by the relay.
+#[async_trait]
+pub trait Outbox: Send + Sync {
+    /// Up to `limit` messages due for delivery, oldest first. They are hidden from
+    /// other relays for `lease`, and handed out again after it if never acknowledged.
+    async fn claim(&self, limit: i64, lease: Duration) -> anyhow::Result<Vec<OutboxMessage>, OutboxError>;
+    async fn mark_published(&self, id: i64) -> anyhow::Result<(), OutboxError>;
+    /// Keeps the message for another attempt at `retry_at`.
+    async fn mark_failed(&self, id: i64, error: &str, retry_at: DateTime<Utc>) -> anyhow::Result<(), OutboxError>;
+}
+
+/// The event describing a change the repository just stored.
+pub(crate) fn event(operation: Operation, old: Option<&DbUser>, new: &DbUser, context: &ChangeContext) -> UserEvent {
+    match (operation, old) {
+        (Operation::Update, Some(old)) => UserEvent::updated(old, new, context),
+        (Operation::Delete, _) => UserEvent::deleted(new.id, &new.city, new.version, new.updated_at, context),
+        (Operation::Restore, _) => UserEvent::restored(new, context),
+        _ => UserEvent::created(new, context),
+    }
+}
+
+/// Writes the event in the transaction of the change, so it is sent if and only if the change commits.
+/// Returns the id of the message.
+pub(crate) async fn enqueue(tx: &mut Transaction<'_, Postgres>, event: &UserEvent) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("INSERT INTO user_outbox (user_id, payload) VALUES ($1, $2) RETURNING id")
+        .bind(event.user_id())
+        .bind(Json(event))
+        .fetch_one(&mut *tx).await
+}
+
+impl<'r> FromRow<'r, PgRow> for OutboxMessage {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let event: Json<UserEvent> = row.try_get("payload")?;
+        Ok(OutboxMessage {
+            id: row.try_get("id")?,
+            user_id: row.try_get("user_id")?,
+            event: event.0,
+            attempts: row.try_get("attempts")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+#[async_trait]
+impl Outbox for PostgresRepository {
+    async fn claim(&self, limit: i64, lease: Duration) -> anyhow::Result<Vec<OutboxMessage>, OutboxError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        // SKIP LOCKED lets several instances drain the outbox without waiting on each other
+        let res = sqlx::query_as::<_, OutboxMessage>("
+        UPDATE user_outbox SET next_attempt_at = now() + make_interval(secs => $2)
+        WHERE id IN (
+            SELECT id FROM user_outbox WHERE published_at IS NULL AND next_attempt_at <= now()
+            ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, user_id, payload, attempts, created_at
+    ").bind(limit)
+            .bind(lease.as_secs_f64())
+            .fetch_all(db_pool).await;
+        match res {
+            Ok(mut messages) => {
+                messages.sort_by_key(|message| message.id);
+                Ok(messages)
+            }
+            Err(_) => Err(OutboxError::Unknown),
+        }
+    }
+
+    async fn mark_published(&self, id: i64) -> anyhow::Result<(), OutboxError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        sqlx::query("UPDATE user_outbox SET published_at = now() WHERE id = $1")
+            .bind(id)
+            .execute(db_pool).await
+            .map(|_| ())
+            .map_err(|_| OutboxError::Unknown)
+    }
+
+    async fn mark_failed(&self, id: i64, error: &str, retry_at: DateTime<Utc>) -> anyhow::Result<(), OutboxError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        sqlx::query("UPDATE user_outbox SET attempts = attempts + 1, last_error = $2, next_attempt_at = $3 WHERE id = $1")
+            .bind(id)
+            .bind(error)
+            .bind(retry_at)
+            .execute(db_pool).await
+            .map(|_| ())
+            .map_err(|_| OutboxError::Unknown)
+    }
+}
@@ -0,0 +1,329 @@
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
+use crate::repository::user::PostgresRepository;
+
+/// A partner endpoint notified of user changes. An empty `events` listens to every event type.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct DbWebhook {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub secret: String,
+    pub active: bool,
+    pub consecutive_failures: i32,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DbWebhook {
+    pub fn listens_to(&self, event_type: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|event| event == event_type)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    /// Given up because the subscription was disabled.
+    Cancelled,
+}
+
+impl std::str::FromStr for DeliveryStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "cancelled" => Ok(DeliveryStatus::Cancelled),
+            _ => Err(()),
+        }
+    }
+}
+
+/// One outbox message for one subscription, with the outcome of its last attempt.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct DbDelivery {
+    pub id: i64,
+    pub subscription_id: Uuid,
+    pub message_id: i64,
+    pub event_type: String,
+    pub payload: Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbDeliveryPage {
+    pub deliveries: Vec<DbDelivery>,
+    pub total: i64,
+}
+
+/// A claimed delivery with where to send it and the key to sign it with.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PendingDelivery {
+    pub delivery: DbDelivery,
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub enum WebhookError {
+    NotFound,
+    Unknown,
+}
+
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    async fn insert_webhook(&self, webhook: DbWebhook) -> anyhow::Result<DbWebhook, WebhookError>;
+    /// Every subscription, disabled ones included, oldest first.
+    async fn fetch_webhooks(&self) -> anyhow::Result<Vec<DbWebhook>, WebhookError>;
+    async fn get_webhook(&self, id: Uuid) -> anyhow::Result<DbWebhook, WebhookError>;
+    /// Removes the subscription with its delivery log.
+    async fn delete_webhook(&self, id: Uuid) -> anyhow::Result<(), WebhookError>;
+    /// Queues the message for each active subscription listening to `event_type`. Queuing
+    /// the same message twice is a no-op, so the outbox relay may retry it.
+    async fn queue_deliveries(&self, message_id: i64, event_type: &str, payload: &Value) -> anyhow::Result<(), WebhookError>;
+    /// Up to `limit` pending deliveries of active subscriptions due now, hidden from other dispatchers for `lease`.
+    async fn claim_deliveries(&self, limit: i64, lease: Duration) -> anyhow::Result<Vec<PendingDelivery>, WebhookError>;
+    /// Closes the delivery and resets the failure count of its subscription.
+    async fn mark_delivered(&self, id: i64, response_status: i32) -> anyhow::Result<(), WebhookError>;
+    /// Schedules another attempt at `retry_at`. Once its subscription failed `max_failures` times
+    /// in a row, it is disabled and its pending deliveries cancelled; returns whether that happened.
+    async fn mark_delivery_failed(
+        &self,
+        id: i64,
+        response_status: Option<i32>,
+        error: &str,
+        retry_at: DateTime<Utc>,
+        max_failures: i32,
+    ) -> anyhow::Result<bool, WebhookError>;
+    /// Deliveries of the subscription, newest first.
+    async fn fetch_deliveries(&self, subscription_id: Uuid, limit: i64, offset: i64) -> anyhow::Result<DbDeliveryPage, WebhookError>;
+}
+
+impl<'r> FromRow<'r, PgRow> for DbWebhook {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(DbWebhook {
+            id: row.try_get("id")?,
+            url: row.try_get("url")?,
+            events: row.try_get("events")?,
+            secret: row.try_get("secret")?,
+            active: row.try_get("active")?,
+            consecutive_failures: row.try_get("consecutive_failures")?,
+            disabled_at: row.try_get("disabled_at")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl<'r> FromRow<'r, PgRow> for DbDelivery {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let status: String = row.try_get("status")?;
+        let status = status.parse().map_err(|_| sqlx::Error::ColumnDecode {
+            index: "status".to_string(),
+            source: format!("unknown delivery status {}", status).into(),
+        })?;
+        let payload: Json<Value> = row.try_get("payload")?;
+        Ok(DbDelivery {
+            id: row.try_get("id")?,
+            subscription_id: row.try_get("subscription_id")?,
+            message_id: row.try_get("message_id")?,
+            event_type: row.try_get("event_type")?,
+            payload: payload.0,
+            status,
+            attempts: row.try_get("attempts")?,
+            response_status: row.try_get("response_status")?,
+            last_error: row.try_get("last_error")?,
+            next_attempt_at: row.try_get("next_attempt_at")?,
+            delivered_at: row.try_get("delivered_at")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+const WEBHOOK_COLUMNS: &str = "id, url, events, secret, active, consecutive_failures, disabled_at, created_at";
+const DELIVERY_COLUMNS: &str = "id, subscription_id, message_id, event_type, payload, status, attempts, response_status, last_error, next_attempt_at, delivered_at, created_at";
+
+#[async_trait]
+impl WebhookRepository for PostgresRepository {
+    async fn insert_webhook(&self, webhook: DbWebhook) -> anyhow::Result<DbWebhook, WebhookError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        sqlx::query_as::<_, DbWebhook>(&format!(
+            "INSERT INTO webhook_subscriptions (id, url, events, secret) VALUES ($1, $2, $3, $4) RETURNING {}",
+            WEBHOOK_COLUMNS,
+        ))
+            .bind(webhook.id)
+            .bind(&webhook.url)
+            .bind(&webhook.events)
+            .bind(&webhook.secret)
+            .fetch_one(db_pool).await
+            .map_err(|_| WebhookError::Unknown)
+    }
+
+    async fn fetch_webhooks(&self) -> anyhow::Result<Vec<DbWebhook>, WebhookError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        sqlx::query_as::<_, DbWebhook>(&format!("SELECT {} FROM webhook_subscriptions ORDER BY created_at, id", WEBHOOK_COLUMNS))
+            .fetch_all(db_pool).await
+            .map_err(|_| WebhookError::Unknown)
+    }
+
+    async fn get_webhook(&self, id: Uuid) -> anyhow::Result<DbWebhook, WebhookError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        let res = sqlx::query_as::<_, DbWebhook>(&format!("SELECT {} FROM webhook_subscriptions WHERE id = $1", WEBHOOK_COLUMNS))
+            .bind(id)
+            .fetch_optional(db_pool).await;
+        match res {
+            Ok(Some(webhook)) => Ok(webhook),
+            Ok(None) => Err(WebhookError::NotFound),
+            Err(_) => Err(WebhookError::Unknown),
+        }
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> anyhow::Result<(), WebhookError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        match sqlx::query("DELETE FROM webhook_subscriptions WHERE id = $1").bind(id).execute(db_pool).await {
+            Ok(done) if done.rows_affected() == 0 => Err(WebhookError::NotFound),
+            Ok(_) => Ok(()),
+            Err(_) => Err(WebhookError::Unknown),
+        }
+    }
+
+    async fn queue_deliveries(&self, message_id: i64, event_type: &str, payload: &Value) -> anyhow::Result<(), WebhookError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        sqlx::query("
+        INSERT INTO webhook_deliveries (subscription_id, message_id, event_type, payload)
+        SELECT id, $1, $2, $3 FROM webhook_subscriptions
+        WHERE active AND (cardinality(events) = 0 OR $2 = ANY(events))
+        ON CONFLICT (subscription_id, message_id) DO NOTHING
+    ").bind(message_id)
+            .bind(event_type)
+            .bind(Json(payload))
+            .execute(db_pool).await
+            .map(|_| ())
+            .map_err(|_| WebhookError::Unknown)
+    }
+
+    async fn claim_deliveries(&self, limit: i64, lease: Duration) -> anyhow::Result<Vec<PendingDelivery>, WebhookError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        let res = sqlx::query(&format!("
+        UPDATE webhook_deliveries d SET next_attempt_at = now() + make_interval(secs => $2)
+        FROM webhook_subscriptions s
+        WHERE s.id = d.subscription_id AND d.id IN (
+            SELECT pending.id FROM webhook_deliveries pending
+            JOIN webhook_subscriptions subscription ON subscription.id = pending.subscription_id
+            WHERE pending.status = 'pending' AND subscription.active AND pending.next_attempt_at <= now()
+            ORDER BY pending.id LIMIT $1 FOR UPDATE OF pending SKIP LOCKED
+        )
+        RETURNING {}, s.url AS url, s.secret AS secret
+    ", DELIVERY_COLUMNS.split(", ").map(|column| format!("d.{}", column)).collect::<Vec<_>>().join(", ")))
+            .bind(limit)
+            .bind(lease.as_secs_f64())
+            .fetch_all(db_pool).await
+            .map_err(|_| WebhookError::Unknown)?;
+        let mut claimed = res.iter()
+            .map(|row| Ok(PendingDelivery {
+                delivery: DbDelivery::from_row(row)?,
+                url: row.try_get("url")?,
+                secret: row.try_get("secret")?,
+            }))
+            .collect::<Result<Vec<_>, sqlx::Error>>()
+            .map_err(|_| WebhookError::Unknown)?;
+        claimed.sort_by_key(|pending| pending.delivery.id);
+        Ok(claimed)
+    }
+
+    async fn mark_delivered(&self, id: i64, response_status: i32) -> anyhow::Result<(), WebhookError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        let mut tx = db_pool.begin().await.map_err(|_| WebhookError::Unknown)?;
+        let subscription_id: Uuid = sqlx::query("
+        UPDATE webhook_deliveries SET status = 'delivered', attempts = attempts + 1, response_status = $2, last_error = NULL, delivered_at = now()
+        WHERE id = $1 RETURNING subscription_id
+    ").bind(id)
+            .bind(response_status)
+            .fetch_optional(&mut tx).await
+            .map_err(|_| WebhookError::Unknown)?
+            .ok_or(WebhookError::NotFound)?
+            .try_get("subscription_id").map_err(|_| WebhookError::Unknown)?;
+        sqlx::query("UPDATE webhook_subscriptions SET consecutive_failures = 0 WHERE id = $1")
+            .bind(subscription_id)
+            .execute(&mut tx).await
+            .map_err(|_| WebhookError::Unknown)?;
+        tx.commit().await.map_err(|_| WebhookError::Unknown)
+    }
+
+    async fn mark_delivery_failed(
+        &self,
+        id: i64,
+        response_status: Option<i32>,
+        error: &str,
+        retry_at: DateTime<Utc>,
+        max_failures: i32,
+    ) -> anyhow::Result<bool, WebhookError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        let mut tx = db_pool.begin().await.map_err(|_| WebhookError::Unknown)?;
+        let subscription_id: Uuid = sqlx::query("
+        UPDATE webhook_deliveries SET attempts = attempts + 1, response_status = $2, last_error = $3, next_attempt_at = $4
+        WHERE id = $1 RETURNING subscription_id
+    ").bind(id)
+            .bind(response_status)
+            .bind(error)
+            .bind(retry_at)
+            .fetch_optional(&mut tx).await
+            .map_err(|_| WebhookError::Unknown)?
+            .ok_or(WebhookError::NotFound)?
+            .try_get("subscription_id").map_err(|_| WebhookError::Unknown)?;
+        let failures: i32 = sqlx::query("
+        UPDATE webhook_subscriptions SET consecutive_failures = consecutive_failures + 1 WHERE id = $1 RETURNING consecutive_failures
+    ").bind(subscription_id)
+            .fetch_one(&mut tx).await
+            .and_then(|row| row.try_get("consecutive_failures"))
+            .map_err(|_| WebhookError::Unknown)?;
+        let disabled = failures >= max_failures;
+        if disabled {
+            sqlx::query("UPDATE webhook_subscriptions SET active = false, disabled_at = now() WHERE id = $1 AND active")
+                .bind(subscription_id)
+                .execute(&mut tx).await
+                .map_err(|_| WebhookError::Unknown)?;
+            sqlx::query("UPDATE webhook_deliveries SET status = 'cancelled' WHERE subscription_id = $1 AND status = 'pending'")
+                .bind(subscription_id)
+                .execute(&mut tx).await
+                .map_err(|_| WebhookError::Unknown)?;
+        }
+        tx.commit().await.map_err(|_| WebhookError::Unknown)?;
+        Ok(disabled)
+    }
+
+    async fn fetch_deliveries(&self, subscription_id: Uuid, limit: i64, offset: i64) -> anyhow::Result<DbDeliveryPage, WebhookError> {
+        let db_pool = self.db_pool.as_ref().unwrap();
+        let total = sqlx::query("SELECT count(*) FROM webhook_deliveries WHERE subscription_id = $1")
+            .bind(subscription_id)
+            .fetch_one(db_pool).await
+            .and_then(|row| row.try_get::<i64, _>(0))
+            .map_err(|_| WebhookError::Unknown)?;
+        let deliveries = sqlx::query_as::<_, DbDelivery>(&format!(
+            "SELECT {} FROM webhook_deliveries WHERE subscription_id = $1 ORDER BY id DESC LIMIT $2 OFFSET $3",
+            DELIVERY_COLUMNS,
+        ))
+            .bind(subscription_id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(db_pool).await
+            .map_err(|_| WebhookError::Unknown)?;
+        Ok(DbDeliveryPage { deliveries, total })
+    }
+}